@@ -0,0 +1,59 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use sdv_volcano::rng::{stardew_seed_mix, DotnetRng};
+use sdv_volcano::{compute_volcano_layouts, do_dungeon, generate_floor, GameSettings};
+
+// the day of the first year with the most luck branches, with every luck buff and the special
+// charm widening the luck range
+fn branchy_settings() -> GameSettings {
+    let mut settings = GameSettings::new();
+    settings.seed = 123456789;
+    settings.has_caldera = true;
+    settings.special_charm = true;
+    settings.max_luck_lvl = 8;
+    settings.days_played = (1..=112)
+        .max_by_key(|&day| {
+            compute_volcano_layouts(GameSettings {
+                days_played: day,
+                ..settings
+            })
+            .len()
+        })
+        .unwrap();
+    settings
+}
+
+fn rng(c: &mut Criterion) {
+    c.bench_function("DotnetRng::new", |b| {
+        b.iter(|| DotnetRng::new(black_box(123456789)))
+    });
+    let values = [1234.0, 5152.0, 61728394.0];
+    c.bench_function("stardew_seed_mix legacy", |b| {
+        b.iter(|| stardew_seed_mix(true, black_box(&values)))
+    });
+    c.bench_function("stardew_seed_mix xxhash", |b| {
+        b.iter(|| stardew_seed_mix(false, black_box(&values)))
+    });
+}
+
+fn dungeon(c: &mut Criterion) {
+    let settings = branchy_settings();
+    let branches = compute_volcano_layouts(settings);
+    println!(
+        "day {}: {} luck branches",
+        settings.days_played,
+        branches.len()
+    );
+    c.bench_function("compute_volcano_layouts", |b| {
+        b.iter(|| compute_volcano_layouts(black_box(settings)))
+    });
+    // DungeonFloorState::new + load_map, plus copying out the result
+    let layout = branches[0].2[4];
+    c.bench_function("generate_floor", |b| {
+        b.iter(|| generate_floor(black_box(settings), 4, layout))
+    });
+    c.bench_function("do_dungeon", |b| b.iter(|| do_dungeon(black_box(settings))));
+}
+
+criterion_group!(benches, rng, dungeon);
+criterion_main!(benches);
@@ -1,14 +1,13 @@
 use std::env;
 use std::fs::File;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Copy, Clone)]
-pub enum SetPieceFeature {
-    Rng,
-    Tooth,
-    Chest,
-}
+// the layout checks in there run on the built-in data in the data_pack tests, and on
+// data packs when they are loaded
+#[path = "src/game_data.rs"]
+#[allow(dead_code)]
+mod game_data;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
@@ -23,136 +22,278 @@ enum MapTile {
     MonsterSpawn = 7,
 }
 
-fn main() {
-    let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
-    let mut out_sizes = File::create(out_dir.join("set_piece_sizes.rs")).unwrap();
-    let mut out_events = File::create(out_dir.join("set_piece_events.rs")).unwrap();
-    let mut out_layouts = File::create(out_dir.join("layouts.bin")).unwrap();
-    writeln!(out_sizes, "match set_size {{").unwrap();
+// the files directly in game_data/ are from 1.6.0, but the volcano came with 1.5 and 1.6 didn't
+// change its maps, so they're used from 1.5 on. data for later versions goes in
+// game_data/<version>/ (e.g. game_data/1.6.9/), with only the files that changed: anything
+// that's not there is taken from game_data/.
+const BASE_VERSION: (u32, u32, u32) = (1, 5, 0);
+
+fn parse_version(name: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = name.split('.').map(|x| x.parse::<u32>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    Some((major, minor, patch))
+}
+
+// raw tile layers of a set piece sheet, for stamping them into exported maps
+fn write_tile_layers(out: &mut String, map: &tiled::Map) {
+    let width = map
+        .layers()
+        .find_map(|x| x.as_tile_layer())
+        .and_then(|x| x.width())
+        .unwrap();
+    *out += &format!("            ({}, &[\n", width);
+    for layer in map.layers() {
+        let Some(tiles) = layer.as_tile_layer() else {
+            continue;
+        };
+        let (width, height) = (
+            tiles.width().unwrap() as i32,
+            tiles.height().unwrap() as i32,
+        );
+        let gids: Vec<u32> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| match tiles.get_tile(x, y) {
+                // there's only one tileset, at firstgid 1
+                Some(tile) => {
+                    (tile.id() + 1)
+                        | (tile.flip_h as u32) << 31
+                        | (tile.flip_v as u32) << 30
+                        | (tile.flip_d as u32) << 29
+                }
+                None => 0,
+            })
+            .collect();
+        *out += &format!("                ({:?}, &{:?}),\n", layer.name, gids);
+    }
+    *out += "            ]),\n";
+}
+
+fn write_set_pieces(out: &mut File, set_size: i32, fname: &Path, map: &tiled::Map) {
+    let paths_layer = map
+        .layers()
+        .find(|x| x.name == "Paths" && matches!(x.layer_type(), tiled::LayerType::Tiles(_)))
+        .unwrap()
+        .as_tile_layer()
+        .unwrap();
+    let num_cols = paths_layer.width().unwrap() as i32 / set_size;
+    let num_rows = paths_layer.height().unwrap() as i32 / set_size;
+    let events = game_data::piece_events(set_size, num_rows, num_cols, |x, y| {
+        paths_layer.get_tile(x, y).map(|tile| tile.id())
+    })
+    .unwrap_or_else(|e| panic!("{}: {}", fname.display(), e));
+    writeln!(out, "            ({num_rows}, {num_cols}, &[").unwrap();
+    for events in events {
+        let events: Vec<_> = events
+            .iter()
+            .map(|x| format!("SetPieceFeature::{:?}", x))
+            .collect();
+        writeln!(out, "                &[{}],", events.join(", ")).unwrap();
+    }
+    writeln!(out, "            ]),").unwrap();
+}
+
+fn write_layouts(out: &mut File, out_dir: &Path, index: usize, fname: &Path) {
+    let layouts = game_data::decode_layouts(&std::fs::read(fname).unwrap())
+        .unwrap_or_else(|e| panic!("{}: {}", fname.display(), e));
+    let packed = game_data::pack_tiles(&layouts);
+    assert_eq!(
+        packed.len(),
+        layouts.len() / (64 * 64) * game_data::PACKED_LAYOUT_SIZE
+    );
+    let bin_name = format!("layouts_{}.bin", index);
+    File::create(out_dir.join(&bin_name))
+        .unwrap()
+        .write_all(&packed)
+        .unwrap();
     writeln!(
-        out_events,
-        "pub fn get_piece_events() -> HashMap<(i32, i32, i32), &'static [SetPieceFeature]> {{
-use SetPieceFeature::*;
-["
+        out,
+        "        layouts: include_bytes!(concat!(env!(\"OUT_DIR\"), \"/{}\")),",
+        bin_name
     )
     .unwrap();
 
-    for set_size in [3, 4, 8, 16, 32] {
-        let fname = format!("game_data/Volcano_SetPieces_{}.tmx", set_size);
-        let map = &tiled::Loader::new().load_tmx_map(&fname).unwrap();
-        println!("cargo::rerun-if-changed={}", fname);
-        let paths_layer = map
-            .layers()
-            .find(|x| x.name == "Paths" && matches!(x.layer_type(), tiled::LayerType::Tiles(_)))
-            .unwrap()
-            .as_tile_layer()
+    // where the set pieces, stairs etc. are, so floors don't need to scan for them
+    writeln!(out, "        layout_info: &[").unwrap();
+    for tiles in layouts.chunks(64 * 64) {
+        writeln!(out, "            [").unwrap();
+        for flip_x in [false, true] {
+            let info = game_data::layout_info(tiles, flip_x);
+            writeln!(
+                out,
+                "                LayoutInfo {{\n                    \
+                 set_pieces: Cow::Borrowed(&{:?}),\n                    \
+                 entrances: Cow::Borrowed(&{:?}),\n                    \
+                 exits: Cow::Borrowed(&{:?}),\n                    \
+                 switch_locations: Cow::Borrowed(&{:?}),\n                    \
+                 monster_spawns: Cow::Borrowed(&{:?}),\n                \
+                 }},",
+                info.set_pieces,
+                info.entrances,
+                info.exits,
+                info.switch_locations,
+                info.monster_spawns
+            )
             .unwrap();
-        let num_cols = paths_layer.width().unwrap() as i32 / set_size;
-        let num_rows = paths_layer.height().unwrap() as i32 / set_size;
-        writeln!(out_sizes, "    {set_size} => ({num_rows}, {num_cols}),").unwrap();
-        for selected_col in 0..num_cols {
-            for selected_row in 0..num_rows {
-                let mut events = vec![];
-                for setx in 0..set_size {
-                    // this really shouldn't be ..=, but that's what the game does
-                    for sety in 0..=set_size {
-                        let src_x = selected_col * set_size + setx;
-                        let src_y = selected_row * set_size + sety;
-                        let tile = paths_layer.get_tile(src_x, src_y);
-                        if let Some(tile) = tile {
-                            match tile.id() {
-                                234..=239 => {
-                                    // possible gate location, random
-                                    events.push(SetPieceFeature::Rng);
-                                }
-                                250 => panic!("set piece contained switch for gate #0"),
-                                // possible setpiece switch location - not worth to track
-                                251..=255 => {}
-                                330 => {}
-                                331 => {}
-                                332 => {
-                                    events.push(SetPieceFeature::Chest);
-                                }
-                                // wall - not even used ingame??
-                                333 => {}
-                                334 => {
-                                    // barrel
-                                    events.push(SetPieceFeature::Rng);
-                                }
-                                335 => {
-                                    events.push(SetPieceFeature::Tooth);
-                                }
-                                // spiker spawn point
-                                346 => {}
-                                _ => {
-                                    panic!("unknown tile on path layer: {}", tile.id());
-                                }
-                            }
-                        }
-                    }
-                }
-                if !events.is_empty() {
-                    writeln!(
-                        out_events,
-                        "    (({}, {}, {}), &{:?} as &[_]),",
-                        set_size, selected_row, selected_col, events
-                    )
-                    .unwrap();
-                }
-            }
         }
+        writeln!(out, "            ],").unwrap();
     }
-    writeln!(out_events, "].into_iter().collect() }}").unwrap();
-    writeln!(out_sizes, "    _ => panic!(\"invalid set size\"),\n}}").unwrap();
+    writeln!(out, "        ],").unwrap();
+}
 
-    println!("cargo::rerun-if-changed=game_data/Layouts.png");
-    let decoder = png::Decoder::new(File::open("game_data/Layouts.png").unwrap());
-    let mut reader = decoder.read_info().unwrap();
-    let mut buf = vec![0; reader.output_buffer_size()];
-    let info = reader.next_frame(&mut buf).unwrap();
-    assert!(
-        info.color_type == png::ColorType::Rgba,
-        "Layouts.png has unexpected color type!"
-    );
-    assert!(
-        info.bit_depth == png::BitDepth::Eight,
-        "Layouts.png has unexpected bit depth!"
-    );
-    let layout_rows = info.height / 64;
-    let layout_cols = info.width / 64;
-    let mut layouts = Vec::new();
-    for layout_y in 0..layout_rows {
-        for layout_x in 0..layout_cols {
-            let this: [[MapTile; 64]; 64] = std::array::from_fn(|y| {
-                std::array::from_fn(|x| {
-                    let px_x = (layout_x as usize) * 64 + x;
-                    let px_y = (layout_y as usize) * 64 + y;
-                    let px_offset = info.line_size * px_y + 4 * px_x;
-                    let color = &buf[px_offset..px_offset + 3];
-                    match color {
-                        [255, 0, 0] => MapTile::Exit,
-                        [0, 255, 0] => MapTile::Enter,
-                        [0, 0, 255] => MapTile::Lava,
-                        [255, 255, 0] => MapTile::SetPiece,
-                        [128, 128, 128] => MapTile::SwitchLocation,
-                        [0, 255, 255] => MapTile::MonsterSpawn,
-                        [0, 0, 0] => MapTile::Wall,
-                        [255, 255, 255] => MapTile::Floor,
-                        _ => panic!("unexpected color in Layouts.png! {:?}", color),
-                    }
-                })
-            });
-            layouts.push(this);
+struct LootLine<'a> {
+    roll: usize,
+    name: &'a str,
+    item: &'a str,
+    count: u32,
+    icon: &'a str,
+    condition: Option<&'a str>,
+}
+
+// one enum per chest from chest_loot.txt, with the tables `roll_chest` uses
+fn write_chest_loot(out: &mut File, fname: &Path) {
+    let text = std::fs::read_to_string(fname).unwrap();
+    let fail =
+        |line: usize, msg: &str| -> ! { panic!("{}:{}: {}", fname.display(), line + 1, msg) };
+    let mut chests: Vec<(&str, Vec<LootLine>)> = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
         }
+        if let Some(chest) = line.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+            chests.push((chest, vec![]));
+            continue;
+        }
+        let Some((_, items)) = chests.last_mut() else {
+            fail(i, "item before the first [chest]");
+        };
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        let [roll, name, item, count, icon, ref rest @ ..] = cols[..] else {
+            fail(i, "expected roll, name, item, count and icon");
+        };
+        let roll: usize = roll.parse().unwrap_or_else(|_| fail(i, "bad roll"));
+        let count: u32 = count.parse().unwrap_or_else(|_| fail(i, "bad count"));
+        let condition = match rest {
+            [] => None,
+            ["cracked_golden_coconut"] => Some("CrackedGoldenCoconut"),
+            _ => fail(i, "unknown condition"),
+        };
+        let last_roll = items.last().map(|x| x.roll);
+        if roll != last_roll.map_or(0, |x| x + 1) && Some(roll) != last_roll {
+            fail(i, "rolls should count up from 0");
+        }
+        if Some(roll) == last_roll && items.last().unwrap().condition != condition {
+            fail(i, "items on the same roll need the same condition");
+        }
+        items.push(LootLine {
+            roll,
+            name,
+            item,
+            count,
+            icon,
+            condition,
+        });
+    }
+
+    for (chest, items) in chests {
+        if items.is_empty() {
+            panic!("{}: [{}] has no items", fname.display(), chest);
+        }
+        writeln!(out, "#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]").unwrap();
+        writeln!(out, "pub enum {} {{", chest).unwrap();
+        for line in &items {
+            writeln!(out, "    {},", line.name).unwrap();
+        }
+        writeln!(out, "}}\n\nimpl {} {{", chest).unwrap();
+        writeln!(out, "    const ITEMS: &'static [ChestItem] = &[").unwrap();
+        for line in &items {
+            writeln!(
+                out,
+                "        ChestItem {{ id: {:?}, count: {}, icon: {:?} }},",
+                line.item, line.count, line.icon
+            )
+            .unwrap();
+        }
+        writeln!(out, "    ];").unwrap();
+        writeln!(out, "    const ROLLS: &'static [ChestRoll<Self>] = &[").unwrap();
+        for roll in 0..=items.last().map_or(0, |x| x.roll) {
+            let on_roll: Vec<_> = items.iter().filter(|x| x.roll == roll).collect();
+            let names: Vec<_> = on_roll
+                .iter()
+                .map(|x| format!("Self::{}", x.name))
+                .collect();
+            let condition = match on_roll[0].condition {
+                Some(x) => format!("Some(LootCondition::{})", x),
+                None => "None".to_string(),
+            };
+            writeln!(
+                out,
+                "        ChestRoll {{ condition: {}, items: &[{}] }},",
+                condition,
+                names.join(", ")
+            )
+            .unwrap();
+        }
+        writeln!(out, "    ];\n}}\n").unwrap();
     }
-    while layouts.last() == Some(&[[MapTile::Wall; 64]; 64]) {
-        layouts.pop();
+}
+
+fn main() {
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    let base_dir = Path::new("game_data");
+    println!("cargo::rerun-if-changed=game_data");
+
+    let mut bundles = vec![(BASE_VERSION, base_dir.to_path_buf())];
+    for entry in std::fs::read_dir(base_dir).unwrap() {
+        let path = entry.unwrap().path();
+        if !path.is_dir() {
+            continue;
+        }
+        let name = path.file_name().unwrap().to_string_lossy();
+        let version = parse_version(&name)
+            .unwrap_or_else(|| panic!("{}: not named after a game version", path.display()));
+        bundles.push((version, path));
     }
-    let bytes: Vec<u8> = layouts
-        .iter()
-        .flatten()
-        .flatten()
-        .map(|&x| x as u8)
-        .collect();
-    out_layouts.write_all(&bytes).unwrap();
+    bundles.sort();
+
+    let mut out_bundles = File::create(out_dir.join("bundles.rs")).unwrap();
+    writeln!(out_bundles, "pub static BUNDLES: &[Bundle] = &[").unwrap();
+    for (index, (version, dir)) in bundles.iter().enumerate() {
+        let file = |name: &str| {
+            let path = dir.join(name);
+            if path.exists() {
+                path
+            } else {
+                base_dir.join(name)
+            }
+        };
+        writeln!(
+            out_bundles,
+            "    Bundle {{\n        version: {:?},\n        set_pieces: [",
+            version
+        )
+        .unwrap();
+        let mut tile_layers = String::new();
+        for set_size in game_data::SET_SIZES {
+            let fname = file(&format!("Volcano_SetPieces_{}.tmx", set_size));
+            let map = tiled::Loader::new().load_tmx_map(&fname).unwrap();
+            write_set_pieces(&mut out_bundles, set_size, &fname, &map);
+            write_tile_layers(&mut tile_layers, &map);
+        }
+        writeln!(out_bundles, "        ],").unwrap();
+        writeln!(
+            out_bundles,
+            "        tile_layers: [\n{}        ],",
+            tile_layers
+        )
+        .unwrap();
+        write_layouts(&mut out_bundles, &out_dir, index, &file("Layouts.png"));
+        writeln!(out_bundles, "    }},").unwrap();
+    }
+    writeln!(out_bundles, "];").unwrap();
+
+    let mut out_loot = File::create(out_dir.join("chest_loot.rs")).unwrap();
+    write_chest_loot(&mut out_loot, &base_dir.join("chest_loot.txt"));
 }
@@ -5,6 +5,6 @@ tsc -b
 
 rm -rf dist
 mkdir -p dist/pkg
-cp index.html index.js dist
+cp index.html index.js worker.js dist
 cp -r icons dist
 cp pkg/sdv_volcano.js pkg/sdv_volcano_bg.wasm dist/pkg
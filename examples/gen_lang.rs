@@ -0,0 +1,151 @@
+//! Regenerate the item name tables in `src/lang/items/` from the game's own string data.
+//!
+//! Run with the game's `Content` folder unpacked to json (e.g. by StardewXnbHack):
+//!
+//!     cargo run --example gen_lang -- "path/to/Content (unpacked)"
+//!
+//! Only 1.6 content works, older versions kept the names in the data files themselves.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+// (our table name, the game's locale suffix)
+const LANGUAGES: &[(&str, &str)] = &[
+    ("en", ""),
+    ("de", "de-DE"),
+    ("es", "es-ES"),
+    ("fr", "fr-FR"),
+    ("pt", "pt-BR"),
+    ("zh", "zh-CN"),
+    ("ja", "ja-JP"),
+    ("ko", "ko-KR"),
+    ("ru", "ru-RU"),
+    ("it", "it-IT"),
+    ("hu", "hu-HU"),
+    ("tr", "tr-TR"),
+];
+
+const DRAGON_TOOTH: &str = "(O)852";
+
+type Error = Box<dyn std::error::Error>;
+
+fn read_json(path: &Path) -> Result<Value, Error> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(serde_json::from_str(text.trim_start_matches('\u{feff}'))?)
+}
+
+// every item a chest can have, in the order of chest_loot.txt, then the dragon tooth
+fn item_ids() -> Result<Vec<String>, Error> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("game_data/chest_loot.txt");
+    let mut ids = vec![];
+    for line in fs::read_to_string(path)?.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+            continue;
+        }
+        let id = line
+            .split_whitespace()
+            .nth(2)
+            .ok_or("bad chest_loot.txt line")?;
+        if !ids.iter().any(|x| x == id) {
+            ids.push(id.to_string());
+        }
+    }
+    ids.push(DRAGON_TOOTH.to_string());
+    Ok(ids)
+}
+
+struct Content {
+    root: PathBuf,
+    data: HashMap<&'static str, Value>,
+    strings: HashMap<(String, &'static str), Value>,
+}
+
+impl Content {
+    fn data(&mut self, name: &'static str) -> Result<&Value, Error> {
+        if !self.data.contains_key(name) {
+            let value = read_json(&self.root.join(format!("Data/{}.json", name)))?;
+            self.data.insert(name, value);
+        }
+        Ok(&self.data[name])
+    }
+
+    // the display name field of an item, which in 1.6 is a [LocalizedText ...] token
+    fn display_name_token(&mut self, qualified_id: &str) -> Result<String, Error> {
+        let (kind, id) = qualified_id
+            .strip_prefix('(')
+            .and_then(|x| x.split_once(')'))
+            .ok_or_else(|| format!("not a qualified item id: {}", qualified_id))?;
+        let missing = || format!("{} isn't in the game data", qualified_id);
+        let token = match kind {
+            "O" => self.data("Objects")?[id]["DisplayName"].as_str(),
+            "W" => self.data("Weapons")?[id]["DisplayName"].as_str(),
+            // these are still slash separated, with the display name in field 6 and 5 (from 0)
+            "B" => self.data("Boots")?[id]
+                .as_str()
+                .and_then(|x| x.split('/').nth(6)),
+            "H" => self.data("hats")?[id]
+                .as_str()
+                .and_then(|x| x.split('/').nth(5)),
+            _ => return Err(format!("unknown item type in {}", qualified_id).into()),
+        };
+        Ok(token.ok_or_else(missing)?.to_string())
+    }
+
+    // resolve "[LocalizedText Strings\Objects:CinderShard_Name]" in one language, None if that
+    // language doesn't have it (the game shows the english one then)
+    fn localized(&mut self, token: &str, locale: &'static str) -> Result<Option<String>, Error> {
+        let path = token
+            .strip_prefix("[LocalizedText ")
+            .and_then(|x| x.strip_suffix(']'))
+            .and_then(|x| x.split_whitespace().next())
+            .ok_or_else(|| format!("expected a [LocalizedText ...] token, got {:?}", token))?;
+        let (asset, key) = path
+            .split_once(':')
+            .ok_or_else(|| format!("bad LocalizedText path {:?}", path))?;
+        let asset = asset.replace('\\', "/");
+        let cache_key = (asset.clone(), locale);
+        if !self.strings.contains_key(&cache_key) {
+            let file = if locale.is_empty() {
+                format!("{}.json", asset)
+            } else {
+                format!("{}.{}.json", asset, locale)
+            };
+            let value = read_json(&self.root.join(file))?;
+            self.strings.insert(cache_key.clone(), value);
+        }
+        Ok(self.strings[&cache_key][key].as_str().map(str::to_string))
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let root = std::env::args()
+        .nth(1)
+        .ok_or("usage: gen_lang <unpacked Content folder>")?;
+    let mut content = Content {
+        root: root.into(),
+        data: HashMap::new(),
+        strings: HashMap::new(),
+    };
+    let ids = item_ids()?;
+    let tokens = ids
+        .iter()
+        .map(|id| content.display_name_token(id))
+        .collect::<Result<Vec<_>, _>>()?;
+    let out_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/lang/items");
+    for &(name, locale) in LANGUAGES {
+        let mut out = String::from(
+            "# generated by examples/gen_lang.rs from the game's string data, don't edit\n\n",
+        );
+        for (id, token) in ids.iter().zip(&tokens) {
+            if let Some(text) = content.localized(token, locale)? {
+                out.push_str(&format!("{} = {}\n", id, text.trim()));
+            }
+        }
+        fs::write(out_dir.join(format!("{}.txt", name)), out)?;
+    }
+    Ok(())
+}
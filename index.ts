@@ -1,13 +1,91 @@
-import wasm_init, { GameSettings, main_update, render_map } from "./pkg/sdv_volcano.js";
+import wasm_init, {
+    DataPack,
+    GameSettings,
+    Language,
+    ReportFormat,
+    chest_items,
+    import_save,
+    loot_items,
+    use_data_pack,
+    validate_layouts,
+} from "./pkg/sdv_volcano.js";
+import type { Job, Reply } from "./worker.js";
+
+// runs jobs in a web worker so the page stays responsive. cancelling kills the worker and starts
+// a new one, since there's no other way to stop wasm code in the middle of a computation.
+class Jobs {
+    private worker: Worker;
+    private next_id = 0;
+    private pending = new Map<
+        number,
+        {
+            resolve: (result: any) => void;
+            reject: (err: Error) => void;
+            progress?: (done: number, total: number) => void;
+        }
+    >();
+    // a new worker needs to be given the modded maps again
+    private data_pack: Job | null = null;
+
+    constructor() {
+        this.start();
+    }
+
+    private start() {
+        this.worker = new Worker("worker.js", { type: "module" });
+        this.worker.onmessage = (ev: MessageEvent<Reply>) => {
+            const reply = ev.data;
+            const job = this.pending.get(reply.id);
+            if (!job) return;
+            if ("progress" in reply) {
+                job.progress?.(...reply.progress);
+                return;
+            }
+            this.pending.delete(reply.id);
+            if ("error" in reply) {
+                job.reject(new Error(reply.error));
+            } else {
+                job.resolve(reply.result);
+            }
+        };
+        if (this.data_pack) {
+            this.worker.postMessage({ id: this.next_id++, job: this.data_pack });
+        }
+    }
+
+    run(job: Job, progress?: (done: number, total: number) => void): Promise<any> {
+        if (job.kind == "data_pack") {
+            this.data_pack = job;
+        }
+        const id = this.next_id++;
+        return new Promise((resolve, reject) => {
+            this.pending.set(id, { resolve, reject, progress });
+            this.worker.postMessage({ id, job });
+        });
+    }
+
+    cancel() {
+        this.worker.terminate();
+        for (const job of this.pending.values()) {
+            job.reject(new Error("cancelled"));
+        }
+        this.pending.clear();
+        this.start();
+    }
+}
+
+function get_language(): Language {
+    return +(document.getElementById("language") as HTMLSelectElement).value;
+}
 
 function get_settings() {
     const get_el = (id: string) => document.getElementById(id) as HTMLInputElement;
-    //document.getElementById("caldera_wrapper").classList.toggle("hidden", !post_1_6_4_el.checked);
+    //document.getElementById("caldera_wrapper").classList.toggle("hidden", version < 2);
     // ^ actually this just looks distracting
     const settings = new GameSettings();
     settings.seed = +get_el("seed").value;
     settings.legacy_rng = get_el("legacy_rng").checked;
-    settings.post_1_6_4 = get_el("post_1_6_4").checked;
+    settings.version = +get_el("game_version").value;
     settings.has_caldera = get_el("has_caldera").checked;
     settings.cracked_golden_coconut = get_el("cracked_coconut").checked;
     settings.max_luck_lvl = Math.max(+get_el("max_luck_lvl").value, 0);
@@ -16,8 +94,21 @@ function get_settings() {
     return settings;
 }
 
+function set_settings(settings: GameSettings) {
+    const get_el = (id: string) => document.getElementById(id) as HTMLInputElement;
+    get_el("seed").value = settings.seed.toString();
+    get_el("legacy_rng").checked = settings.legacy_rng;
+    get_el("game_version").value = settings.version.toString();
+    get_el("has_caldera").checked = settings.has_caldera;
+    get_el("cracked_coconut").checked = settings.cracked_golden_coconut;
+    get_el("days_played").value = settings.days_played.toString();
+    get_el("special_charm").checked = settings.special_charm;
+    get_el("max_luck_lvl").value = settings.max_luck_lvl.toString();
+}
+
 async function main() {
     await wasm_init();
+    const jobs = new Jobs();
 
     const small_tiles = new Image();
     small_tiles.src = "icons/maptiles_8.png";
@@ -30,6 +121,8 @@ async function main() {
 
     let last_lvl = null;
     let last_layout = null;
+    // layouts the player shift-clicked, as [level, layout]
+    const observed: [number, number][] = [];
 
     const reset_canvas = () => {
         last_layout = null;
@@ -41,14 +134,35 @@ async function main() {
 
     reset_canvas();
 
-    const select_layout_handler = (lvl: number, layout: number) => {
+    const select_layout_handler = async (lvl: number, layout: number) => {
         const use_big = (document.getElementById("big_tiles") as HTMLInputElement)
             .checked;
         const settings = get_settings();
         const tile_size = use_big ? 16 : 8;
-        map_canvas.width = map_canvas.height = 64 * tile_size;
         const tiles_img = use_big ? big_tiles : small_tiles;
-        const notes = render_map(settings, lvl, layout, map_ctx, tiles_img, tile_size);
+        const [tiles, notes]: [Uint8Array, string] = await jobs.run({
+            kind: "map",
+            code: settings.to_code(),
+            lvl,
+            layout,
+            lang: get_language(),
+        });
+        map_canvas.width = map_canvas.height = 64 * tile_size;
+        for (let y = 0; y < 64; y++) {
+            for (let x = 0; x < 64; x++) {
+                map_ctx.drawImage(
+                    tiles_img,
+                    tiles[y * 64 + x] * tile_size,
+                    0,
+                    tile_size,
+                    tile_size,
+                    x * tile_size,
+                    y * tile_size,
+                    tile_size,
+                    tile_size,
+                );
+            }
+        }
         last_lvl = lvl;
         last_layout = layout;
         map_canvas.classList.remove("hidden");
@@ -62,16 +176,54 @@ async function main() {
         }
     });
 
-    const update = () => {
+    if (location.hash.length > 1) {
+        try {
+            set_settings(GameSettings.from_code(location.hash));
+        } catch (e) {
+            console.log(`ignoring settings in url: ${e}`);
+        }
+    }
+
+    // only the newest update gets shown, older ones that finish later are dropped
+    let update_count = 0;
+    const update = async () => {
         const settings = get_settings();
-        const res = main_update(settings);
-        document.getElementById("temp").innerText = res;
+        const code = settings.to_code();
+        history.replaceState(null, "", "#" + code);
+        const this_update = ++update_count;
+        let res: [string, string, string];
+        try {
+            res = await jobs.run({
+                kind: "update",
+                code,
+                observed: [...observed],
+                lang: get_language(),
+            });
+        } catch (e) {
+            if (e.message == "cancelled" || this_update != update_count) return;
+            // e.g. no map data for the game version, there's nothing to show then
+            document.getElementById("temp").innerText = e.message;
+            document.getElementById("goodies").innerHTML = "";
+            document.getElementById("map-sel").innerHTML = "";
+            reset_canvas();
+            return;
+        }
+        if (this_update != update_count) return;
+        const [text, goodies, layouts] = res;
+        document.getElementById("temp").innerText = text;
+        document.getElementById("goodies").innerHTML = goodies;
+        document.getElementById("map-sel").innerHTML = layouts;
         reset_canvas();
         for (const el of document.getElementsByClassName("layout-btn")) {
             el.addEventListener("click", (ev) => {
                 const el = ev.currentTarget as HTMLElement;
                 const lvl = +el.getAttribute("data-lvl");
                 const layout = +el.getAttribute("data-layout");
+                if (ev.shiftKey) {
+                    observed.push([lvl, layout]);
+                    update();
+                    return;
+                }
                 select_layout_handler(lvl, layout);
                 for (const e2 of document.getElementsByClassName("layout_curr")) {
                     e2.classList.remove("layout_curr");
@@ -83,10 +235,281 @@ async function main() {
 
     update();
 
+    document.getElementById("language").addEventListener("input", async () => {
+        // the map goes away on update, so remember what was shown
+        const [lvl, layout] = [last_lvl, last_layout];
+        await update();
+        if (typeof lvl == "number") {
+            select_layout_handler(lvl, layout);
+        }
+    });
+
     for (const el of document.getElementsByClassName("setting")) {
-        el.addEventListener("input", update);
+        el.addEventListener("input", () => {
+            observed.length = 0;
+            update();
+        });
     }
 
+    const download = (contents: string, type: string, name: string) => {
+        const link = document.createElement("a");
+        link.href = URL.createObjectURL(new Blob([contents], { type }));
+        link.download = name;
+        link.click();
+        setTimeout(() => URL.revokeObjectURL(link.href), 1000);
+    };
+
+    document.getElementById("download-overview").addEventListener("click", async () => {
+        const settings = get_settings();
+        let overviews: string[];
+        try {
+            overviews = await jobs.run({ kind: "overview", code: settings.to_code() });
+        } catch (e) {
+            if (e.message != "cancelled") alert(`overview failed: ${e.message}`);
+            return;
+        }
+        overviews.forEach((svg, i) => {
+            const suffix = overviews.length > 1 ? `_${i + 1}` : "";
+            const name = `volcano_${settings.seed}_day${settings.days_played}${suffix}.svg`;
+            download(svg, "image/svg+xml", name);
+        });
+    });
+
+    const download_report = async (format: ReportFormat, ext: string, mime: string) => {
+        const settings = get_settings();
+        let text: string;
+        try {
+            text = await jobs.run({
+                kind: "report",
+                code: settings.to_code(),
+                observed: [...observed],
+                format,
+            });
+        } catch (e) {
+            if (e.message != "cancelled") alert(`report failed: ${e.message}`);
+            return;
+        }
+        download(text, mime, `volcano_${settings.seed}_day${settings.days_played}.${ext}`);
+    };
+    document.getElementById("report-md").addEventListener("click", () => {
+        download_report(ReportFormat.Markdown, "md", "text/markdown");
+    });
+    document.getElementById("report-html").addEventListener("click", () => {
+        download_report(ReportFormat.Html, "html", "text/html");
+    });
+
+    // the jobs that take a while have a button, a progress bar and a cancel button, with the
+    // ids `name`, `name_progress` and `name_cancel`. the button is off while the job runs.
+    const long_job = (name: string) => {
+        const btn = document.getElementById(name) as HTMLButtonElement;
+        const bar = document.getElementById(`${name}_progress`) as HTMLProgressElement;
+        const cancel = document.getElementById(`${name}_cancel`);
+        cancel.addEventListener("click", () => {
+            jobs.cancel();
+            // this also killed whatever update was running
+            update();
+        });
+        return async (job: Job) => {
+            btn.disabled = true;
+            bar.value = 0;
+            bar.classList.remove("hidden");
+            cancel.classList.remove("hidden");
+            try {
+                return await jobs.run(job, (done, total) => {
+                    bar.max = total;
+                    bar.value = done;
+                });
+            } finally {
+                btn.disabled = false;
+                bar.classList.add("hidden");
+                cancel.classList.add("hidden");
+            }
+        };
+    };
+
+    const run_csv = long_job("csv");
+    document.getElementById("csv").addEventListener("click", async () => {
+        const settings = get_settings();
+        const num_days = Math.max(+(document.getElementById("csv_days") as HTMLInputElement).value, 1);
+        const first = settings.days_played;
+        const last = first + num_days - 1;
+        let csv: string;
+        try {
+            csv = await run_csv({ kind: "csv", code: settings.to_code(), first, last });
+        } catch (e) {
+            if (e.message != "cancelled") alert(`scan failed: ${e.message}`);
+            return;
+        }
+        download(csv, "text/csv", `volcano_${settings.seed}_days${first}-${last}.csv`);
+    });
+
+    const good_days_item = document.getElementById("good_days_item") as HTMLSelectElement;
+    const fill_items = () => {
+        const selected = good_days_item.value;
+        good_days_item.innerHTML = "";
+        for (const [id, name] of chest_items(get_language())) {
+            good_days_item.add(new Option(name, id, false, id == selected));
+        }
+    };
+    fill_items();
+    document.getElementById("language").addEventListener("input", fill_items);
+
+    const run_good_days = long_job("good_days");
+    document.getElementById("good_days").addEventListener("click", async () => {
+        const get_el = (id: string) => document.getElementById(id) as HTMLInputElement;
+        const list = document.getElementById("good_days_list");
+        list.innerHTML = "";
+        try {
+            const days: string[] = await run_good_days({
+                kind: "good_days",
+                code: get_settings().to_code(),
+                condition: +get_el("good_days_kind").value,
+                item: good_days_item.value,
+                teeth: Math.max(+get_el("good_days_teeth").value, 1),
+                count: Math.max(+get_el("good_days_count").value, 1),
+            });
+            for (const day of days) {
+                const li = document.createElement("li");
+                li.innerText = day;
+                list.append(li);
+            }
+            if (days.length == 0) {
+                list.innerText = "none in the next year";
+            }
+        } catch (e) {
+            if (e.message != "cancelled") alert(`search failed: ${e.message}`);
+        }
+    });
+
+    // [item id, weight]
+    const wanted: [string, number][] = [];
+    const plan_item = document.getElementById("plan_item") as HTMLSelectElement;
+    const plan_wanted = document.getElementById("plan_wanted");
+    const item_names = new Map<string, string>();
+    const show_wanted = () => {
+        plan_wanted.innerHTML = "";
+        wanted.forEach(([id, weight], i) => {
+            const li = document.createElement("li");
+            li.innerText = `${item_names.get(id)}, weight ${weight} `;
+            const remove = document.createElement("button");
+            remove.innerText = "remove";
+            remove.addEventListener("click", () => {
+                wanted.splice(i, 1);
+                show_wanted();
+            });
+            li.append(remove);
+            plan_wanted.append(li);
+        });
+    };
+    const fill_plan_items = () => {
+        const selected = plan_item.value;
+        plan_item.innerHTML = "";
+        for (const [id, name] of loot_items(get_language())) {
+            item_names.set(id, name);
+            plan_item.add(new Option(name, id, false, id == selected));
+        }
+        show_wanted();
+    };
+    fill_plan_items();
+    document.getElementById("language").addEventListener("input", fill_plan_items);
+
+    document.getElementById("plan_add").addEventListener("click", () => {
+        const weight = Math.max(+(document.getElementById("plan_weight") as HTMLInputElement).value, 0);
+        const existing = wanted.find((x) => x[0] == plan_item.value);
+        if (existing) {
+            existing[1] = weight;
+        } else {
+            wanted.push([plan_item.value, weight]);
+        }
+        show_wanted();
+    });
+
+    const run_plan = long_job("plan");
+    document.getElementById("plan").addEventListener("click", async () => {
+        const get_el = (id: string) => document.getElementById(id) as HTMLInputElement;
+        const list = document.getElementById("plan_list");
+        const settings = get_settings();
+        const luck_lvls = get_el("plan_luck_lvls")
+            .value.split(/[\s,]+/)
+            .filter((x) => x != "")
+            .map((x) => Math.max(+x, 0))
+            .filter((x) => Number.isInteger(x));
+        if (wanted.length == 0 || luck_lvls.length == 0) {
+            list.innerText = "add some items and luck buff levels first";
+            return;
+        }
+        list.innerHTML = "";
+        try {
+            const lines: string[] = await run_plan({
+                kind: "plan",
+                code: settings.to_code(),
+                first: settings.days_played,
+                last: settings.days_played + Math.max(+get_el("plan_days").value, 1) - 1,
+                max_trips: Math.max(+get_el("plan_trips").value, 1),
+                wanted: [...wanted],
+                luck_lvls,
+                lang: get_language(),
+            });
+            for (const line of lines) {
+                const li = document.createElement("li");
+                li.innerText = line;
+                list.append(li);
+            }
+        } catch (e) {
+            if (e.message != "cancelled") alert(`planning failed: ${e.message}`);
+        }
+    });
+
+    document.body.addEventListener("dragover", (ev) => ev.preventDefault());
+    document.body.addEventListener("drop", async (ev) => {
+        ev.preventDefault();
+        // modded map files go into a data pack, anything else should be a save. the pack is
+        // built here too, to catch errors and to check the layouts.
+        let pack: DataPack | null = null;
+        const pack_job: Extract<Job, { kind: "data_pack" }> = {
+            kind: "data_pack",
+            layouts: null,
+            set_pieces: [],
+        };
+        for (const file of ev.dataTransfer.files) {
+            try {
+                const set_pieces = file.name.match(/^Volcano_SetPieces_(\d+)\.tmx$/);
+                if (file.name == "Layouts.png") {
+                    pack ??= new DataPack();
+                    pack_job.layouts = new Uint8Array(await file.arrayBuffer());
+                    pack.set_layouts(pack_job.layouts);
+                } else if (set_pieces) {
+                    pack ??= new DataPack();
+                    const tmx = await file.text();
+                    pack.set_set_pieces(+set_pieces[1], tmx);
+                    pack_job.set_pieces.push([+set_pieces[1], tmx]);
+                } else {
+                    set_settings(import_save(get_settings(), await file.text()));
+                }
+            } catch (e) {
+                alert(`couldn't read ${file.name}: ${e}`);
+                return;
+            }
+        }
+        if (pack) {
+            use_data_pack(pack);
+            pack.free();
+            jobs.run(pack_job);
+            const anomalies = document.getElementById("pack_anomalies");
+            anomalies.innerHTML = "";
+            for (const anomaly of validate_layouts()) {
+                const li = document.createElement("li");
+                li.innerText = anomaly;
+                anomalies.append(li);
+            }
+            document
+                .getElementById("pack_notes")
+                .classList.toggle("hidden", anomalies.childElementCount == 0);
+        }
+        observed.length = 0;
+        update();
+    });
+
     document.getElementById("spam")?.addEventListener("click", () => {
         for (let i = 0; i < 1000; i++) update();
     });
@@ -0,0 +1,76 @@
+use std::fmt::Write;
+
+use crate::{generate_floor, DataPackError, GameSettings, GeneratedFloor, Goodie, MapTile};
+
+// xterm 256-color palette indices
+fn tile_color(tile: MapTile) -> u8 {
+    match tile {
+        MapTile::Floor => 236,
+        MapTile::Lava => 202,
+        MapTile::Wall => 16,
+        MapTile::Enter => 34,
+        MapTile::Exit => 160,
+        MapTile::SetPiece => 95,
+        MapTile::SwitchLocation => 245,
+        MapTile::MonsterSpawn => 30,
+    }
+}
+
+fn goodie_glyph(goodie: &Goodie) -> char {
+    match goodie {
+        Goodie::DragonTooth => 'T',
+        Goodie::CommonChest(_) => 'C',
+        Goodie::RareChest(_) => 'R',
+        Goodie::ChanceChest { .. } => '?',
+    }
+}
+
+/// Draw a floor with colored block characters, two columns per tile so it comes out roughly square.
+/// Stairs, buttons and loot get a glyph on top; a legend with the loot follows the map.
+pub fn render_floor_ansi(floor: &GeneratedFloor) -> String {
+    let mut glyphs = [[None; 64]; 64];
+    for (x, y, goodie) in floor.loot_tiles() {
+        glyphs[y as usize][x as usize] = Some(goodie_glyph(goodie));
+    }
+    let mut out = String::new();
+    for y in 0..64 {
+        let mut last_color = None;
+        for x in 0..64 {
+            let tile = floor.tiles[(x, y)];
+            let color = tile_color(tile);
+            let glyph = glyphs[y as usize][x as usize].or(match tile {
+                MapTile::Enter => Some('<'),
+                MapTile::Exit => Some('>'),
+                MapTile::SwitchLocation => Some('b'),
+                _ => None,
+            });
+            match glyph {
+                Some(c) => {
+                    write!(out, "\x1b[0;1;97;48;5;{}m{} \x1b[0m", color, c).unwrap();
+                    last_color = None;
+                }
+                None => {
+                    if last_color != Some(color) {
+                        write!(out, "\x1b[38;5;{}m", color).unwrap();
+                        last_color = Some(color);
+                    }
+                    out += "\u{2588}\u{2588}";
+                }
+            }
+        }
+        out += "\x1b[0m\n";
+    }
+    out += "< entrance  > stairs down  b possible button\n";
+    for (_, goodie) in &floor.loot {
+        writeln!(out, "{} {}", goodie_glyph(goodie), goodie.describe()).unwrap();
+    }
+    out
+}
+
+pub fn render_map_ansi(
+    settings: GameSettings,
+    lvl: i32,
+    layout: u32,
+) -> Result<String, DataPackError> {
+    Ok(render_floor_ansi(&generate_floor(settings, lvl, layout)?))
+}
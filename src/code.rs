@@ -0,0 +1,244 @@
+use std::fmt::Display;
+
+use base64::Engine;
+use wasm_bindgen::prelude::*;
+
+use crate::{GameSettings, GameVersion};
+
+// bump this and add a new branch to `decode` whenever the layout below changes, old codes
+// need to keep working
+const VERSION: u8 = 2;
+
+const LEGACY_RNG: u8 = 1 << 0;
+const HAS_CALDERA: u8 = 1 << 1;
+// version 1 only, version 2 has a byte for the game version
+const POST_1_6_4: u8 = 1 << 2;
+const CRACKED_GOLDEN_COCONUT: u8 = 1 << 3;
+const SPECIAL_CHARM: u8 = 1 << 4;
+// version 1 only
+const PRE_1_6: u8 = 1 << 5;
+
+const BASE64: base64::engine::GeneralPurpose = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodeError {
+    NotBase64,
+    Empty,
+    UnknownVersion(u8),
+    UnknownGameVersion(u8),
+    Truncated,
+}
+
+impl Display for CodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodeError::NotBase64 => write!(f, "settings code contains invalid characters"),
+            CodeError::Empty => write!(f, "settings code is empty"),
+            CodeError::UnknownVersion(v) => write!(
+                f,
+                "settings code is version {}, which is newer than this version understands",
+                v
+            ),
+            CodeError::UnknownGameVersion(v) => {
+                write!(f, "settings code has an unknown game version ({})", v)
+            }
+            CodeError::Truncated => write!(f, "settings code is cut off"),
+        }
+    }
+}
+
+impl std::error::Error for CodeError {}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &mut &[u8]) -> Result<u32, CodeError> {
+    let mut value = 0_u32;
+    for shift in (0..35).step_by(7) {
+        let (&byte, rest) = data.split_first().ok_or(CodeError::Truncated)?;
+        *data = rest;
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(CodeError::Truncated)
+}
+
+fn read_bytes<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], CodeError> {
+    if data.len() < N {
+        return Err(CodeError::Truncated);
+    }
+    let (bytes, rest) = data.split_at(N);
+    *data = rest;
+    Ok(bytes.try_into().unwrap())
+}
+
+fn settings_from_flags(seed: i32, flags: u8, version: GameVersion) -> GameSettings {
+    GameSettings {
+        seed,
+        legacy_rng: flags & LEGACY_RNG != 0,
+        has_caldera: flags & HAS_CALDERA != 0,
+        version,
+        cracked_golden_coconut: flags & CRACKED_GOLDEN_COCONUT != 0,
+        special_charm: flags & SPECIAL_CHARM != 0,
+        days_played: 0,
+        max_luck_lvl: 0,
+    }
+}
+
+// version 1: seed (i32 le), flags, days played (varint), max luck level (varint)
+fn decode_v1(mut data: &[u8]) -> Result<GameSettings, CodeError> {
+    let seed = i32::from_le_bytes(read_bytes(&mut data)?);
+    let [flags] = read_bytes(&mut data)?;
+    // both could be set, 1.6.4 used to win
+    let version = if flags & POST_1_6_4 != 0 {
+        GameVersion::V1_6_4
+    } else if flags & PRE_1_6 != 0 {
+        GameVersion::V1_5
+    } else {
+        GameVersion::V1_6
+    };
+    Ok(GameSettings {
+        days_played: read_varint(&mut data)?,
+        max_luck_lvl: read_varint(&mut data)?,
+        ..settings_from_flags(seed, flags, version)
+    })
+}
+
+// version 2: seed (i32 le), flags, game version, days played (varint), max luck level (varint)
+fn decode_v2(mut data: &[u8]) -> Result<GameSettings, CodeError> {
+    let seed = i32::from_le_bytes(read_bytes(&mut data)?);
+    let [flags, version] = read_bytes(&mut data)?;
+    let version = match version {
+        0 => GameVersion::V1_5,
+        1 => GameVersion::V1_6,
+        2 => GameVersion::V1_6_4,
+        v => return Err(CodeError::UnknownGameVersion(v)),
+    };
+    Ok(GameSettings {
+        days_played: read_varint(&mut data)?,
+        max_luck_lvl: read_varint(&mut data)?,
+        ..settings_from_flags(seed, flags, version)
+    })
+}
+
+impl GameSettings {
+    pub fn decode(code: &str) -> Result<GameSettings, CodeError> {
+        let data = BASE64
+            .decode(code.trim().trim_start_matches('#'))
+            .map_err(|_| CodeError::NotBase64)?;
+        let (&version, rest) = data.split_first().ok_or(CodeError::Empty)?;
+        match version {
+            1 => decode_v1(rest),
+            2 => decode_v2(rest),
+            v => Err(CodeError::UnknownVersion(v)),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl GameSettings {
+    /// Short url-safe string with all the settings, for sharing.
+    pub fn to_code(&self) -> String {
+        let mut data = vec![VERSION];
+        data.extend(self.seed.to_le_bytes());
+        let flags = [
+            (self.legacy_rng, LEGACY_RNG),
+            (self.has_caldera, HAS_CALDERA),
+            (self.cracked_golden_coconut, CRACKED_GOLDEN_COCONUT),
+            (self.special_charm, SPECIAL_CHARM),
+        ];
+        data.push(flags.iter().filter(|x| x.0).fold(0, |acc, x| acc | x.1));
+        data.push(match self.version {
+            GameVersion::V1_5 => 0,
+            GameVersion::V1_6 => 1,
+            GameVersion::V1_6_4 => 2,
+        });
+        write_varint(&mut data, self.days_played);
+        write_varint(&mut data, self.max_luck_lvl);
+        BASE64.encode(data)
+    }
+
+    pub fn from_code(code: &str) -> Result<GameSettings, JsError> {
+        Ok(Self::decode(code)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let settings = GameSettings {
+            seed: -123456789,
+            legacy_rng: false,
+            has_caldera: true,
+            version: GameVersion::V1_6_4,
+            cracked_golden_coconut: true,
+            special_charm: false,
+            days_played: 1_000_000,
+            max_luck_lvl: 300,
+        };
+        assert_eq!(GameSettings::decode(&settings.to_code()), Ok(settings));
+        let code = format!("#{}\n", settings.to_code());
+        assert_eq!(GameSettings::decode(&code), Ok(settings));
+        for version in [GameVersion::V1_5, GameVersion::V1_6] {
+            let settings = GameSettings {
+                version,
+                ..settings
+            };
+            assert_eq!(GameSettings::decode(&settings.to_code()), Ok(settings));
+        }
+    }
+
+    #[test]
+    fn decode_fixed_v1() {
+        let settings = GameSettings::decode("ARXNWwcTyAEI").unwrap();
+        assert_eq!(
+            settings,
+            GameSettings {
+                seed: 123456789,
+                legacy_rng: true,
+                has_caldera: true,
+                version: GameVersion::V1_6,
+                cracked_golden_coconut: false,
+                special_charm: true,
+                days_played: 200,
+                max_luck_lvl: 8,
+            }
+        );
+    }
+
+    #[test]
+    fn decode_v1_versions() {
+        let version = |code| GameSettings::decode(code).unwrap().version;
+        assert_eq!(version("ARXNWwchyAEI"), GameVersion::V1_5);
+        // 1.5 and 1.6.4 both set
+        assert_eq!(version("ARXNWwclyAEI"), GameVersion::V1_6_4);
+    }
+
+    #[test]
+    fn bad_codes() {
+        assert_eq!(GameSettings::decode(""), Err(CodeError::Empty));
+        assert_eq!(GameSettings::decode("AR*"), Err(CodeError::NotBase64));
+        assert_eq!(
+            GameSettings::decode("ARXNWwcTyA"),
+            Err(CodeError::Truncated)
+        );
+        assert_eq!(
+            GameSettings::decode("_w"),
+            Err(CodeError::UnknownVersion(255))
+        );
+        assert_eq!(
+            GameSettings::decode("AgAAAAAACQEA"),
+            Err(CodeError::UnknownGameVersion(9))
+        );
+    }
+}
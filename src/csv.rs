@@ -0,0 +1,156 @@
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    display_luck, do_dungeon, format_date, is_monster_floor, is_mushroom_floor, DataPackError,
+    GameSettings, Goodie,
+};
+
+const HEADER: &str = "day,date,floor,min_luck,max_luck,layout,floor_type,rare_chests,\
+                      common_chests,dragon_teeth";
+
+fn field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// One row per day, floor and luck range where the loot differs, for sorting and filtering in
+/// a spreadsheet. Luck values are in the same units as on the page. Days start at 1, so a range
+/// starting at 0 starts at 1 instead.
+pub fn scan_days_csv(
+    settings: GameSettings,
+    days: RangeInclusive<u32>,
+) -> Result<String, DataPackError> {
+    Ok(format!("{}\n{}", HEADER, day_rows(settings, days)?))
+}
+
+fn day_rows(settings: GameSettings, days: RangeInclusive<u32>) -> Result<String, DataPackError> {
+    let mut out = String::new();
+    for day in *days.start().max(&1)..=*days.end() {
+        let prediction = do_dungeon(GameSettings {
+            days_played: day,
+            ..settings
+        })?;
+        let date = field(&format_date(day));
+        for (lvl, loots) in prediction.loots.iter().enumerate() {
+            // identical loot on neighbouring layouts gets merged into one range, so split
+            // the loot ranges back up by layout
+            let rows = prediction.layouts[lvl]
+                .iter()
+                .flat_map(|&(lmin, lmax, layout)| {
+                    loots
+                        .iter()
+                        .filter(move |x| x.0 <= lmax && lmin <= x.1)
+                        .map(move |x| (x.0.max(lmin), x.1.min(lmax), layout, &x.2))
+                });
+            for (minluck, maxluck, layout, loot) in rows {
+                let floor_type = if is_mushroom_floor(layout) {
+                    "mushroom"
+                } else if is_monster_floor(layout) {
+                    "monster"
+                } else {
+                    ""
+                };
+                let rare: Vec<_> = loot
+                    .iter()
+                    .filter_map(|x| match x {
+                        Goodie::RareChest(c) => Some(c.to_string()),
+                        _ => None,
+                    })
+                    .collect();
+                let common: Vec<_> = loot
+                    .iter()
+                    .filter_map(|x| match x {
+                        Goodie::CommonChest(c) => Some(c.to_string()),
+                        _ => None,
+                    })
+                    .collect();
+                let teeth = loot.iter().filter(|x| **x == Goodie::DragonTooth).count();
+                writeln!(
+                    out,
+                    "{},{},{},{:.4},{:.4},{},{},{},{},{}",
+                    day,
+                    date,
+                    lvl,
+                    display_luck(minluck),
+                    display_luck(maxluck),
+                    layout,
+                    floor_type,
+                    field(&rare.join("; ")),
+                    field(&common.join("; ")),
+                    teeth
+                )
+                .unwrap();
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[wasm_bindgen]
+pub fn days_csv(settings: GameSettings, first_day: u32, last_day: u32) -> Result<String, JsError> {
+    Ok(scan_days_csv(settings, first_day..=last_day)?)
+}
+
+/// Just the rows of `days_csv`, without the header, for building a long scan in pieces.
+#[wasm_bindgen]
+pub fn days_csv_rows(
+    settings: GameSettings,
+    first_day: u32,
+    last_day: u32,
+) -> Result<String, JsError> {
+    Ok(day_rows(settings, first_day..=last_day)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_day() {
+        let settings = GameSettings {
+            seed: 123456789,
+            max_luck_lvl: 2,
+            ..GameSettings::new()
+        };
+        // floors 2 and 4 change layout at the same luck, floor 4's chest changes again later.
+        // the date has a comma in it, so it's quoted
+        assert_eq!(
+            scan_days_csv(settings, 22..=22).unwrap(),
+            "\
+day,date,floor,min_luck,max_luck,layout,floor_type,rare_chests,common_chests,dragon_teeth
+22,\"spring 22, Y1\",0,-0.1000,0.2400,0,,,,0
+22,\"spring 22, Y1\",1,-0.1000,0.2400,15,,,,0
+22,\"spring 22, Y1\",2,-0.1000,-0.0625,25,,,,0
+22,\"spring 22, Y1\",2,-0.0625,0.2400,32,mushroom,,,0
+22,\"spring 22, Y1\",3,-0.1000,0.2400,10,,,,0
+22,\"spring 22, Y1\",4,-0.1000,-0.0625,28,,,Protection Ring,2
+22,\"spring 22, Y1\",4,-0.0625,0.1282,24,,,Pineapple Seeds (5),0
+22,\"spring 22, Y1\",4,0.1282,0.2400,24,,Phoenix Ring,,0
+22,\"spring 22, Y1\",5,-0.1000,0.2400,31,,,,0
+22,\"spring 22, Y1\",6,-0.1000,0.2400,12,,,,0
+22,\"spring 22, Y1\",7,-0.1000,0.2400,1,,,,0
+22,\"spring 22, Y1\",8,-0.1000,0.2400,4,,,,3
+22,\"spring 22, Y1\",9,-0.1000,0.2400,30,,Mermaid Boots,,0
+"
+        );
+        // a range starting at day 0 is the same as one starting at day 1
+        assert_eq!(
+            scan_days_csv(settings, 0..=1).unwrap(),
+            scan_days_csv(settings, 1..=1).unwrap()
+        );
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(field("Phoenix Ring"), "Phoenix Ring");
+        assert_eq!(field("a, b"), "\"a, b\"");
+        assert_eq!(field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(field("two\nlines"), "\"two\nlines\"");
+    }
+}
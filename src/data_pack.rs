@@ -0,0 +1,593 @@
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use wasm_bindgen::prelude::*;
+
+use crate::game_data::{self, AnomalyKind, LayoutAnomaly, LayoutInfo, PACKED_LAYOUT_SIZE};
+use crate::map_data::{self, set_size_index, Bundle, SetPieceFeature, SET_SIZES};
+use crate::{i18n, GameSettings, Language};
+
+// the game picks from layouts 0 to 57 no matter how many there are in the file
+const NUM_LAYOUTS: usize = 58;
+const LAYOUT_SIZE: usize = 64 * 64;
+const FLIP_FLAGS: u32 = 0xe000_0000;
+
+#[derive(Debug)]
+pub enum DataPackError {
+    Io(std::io::Error),
+    Layouts(String),
+    SetPieces {
+        size: i32,
+        message: String,
+    },
+    /// no data pack is in use, and there's no built-in map data for this game version
+    NoMapData {
+        version: (u32, u32, u32),
+    },
+}
+
+impl Display for DataPackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataPackError::Io(e) => write!(f, "couldn't read data pack: {}", e),
+            DataPackError::Layouts(e) => write!(f, "Layouts.png: {}", e),
+            DataPackError::SetPieces { size, message } => {
+                write!(f, "Volcano_SetPieces_{}.tmx: {}", size, message)
+            }
+            DataPackError::NoMapData { version } => write!(
+                f,
+                "there's no map data for version {}.{} built in, load the game's Layouts.png and \
+                 Volcano_SetPieces_*.tmx from that version as a data pack to predict it",
+                version.0, version.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DataPackError {}
+
+impl From<std::io::Error> for DataPackError {
+    fn from(e: std::io::Error) -> Self {
+        DataPackError::Io(e)
+    }
+}
+
+/// The map data that floors are generated from: the layouts from `Layouts.png` and the set
+/// pieces from the `Volcano_SetPieces_*.tmx` sheets. The built-in pack is the unmodded game's;
+/// a new pack starts out as a copy of it, so mods that only replace some files work too.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct DataPack {
+    // packed, 3 bits per tile
+    layouts: Cow<'static, [u8]>,
+    // [not flipped, flipped] for each layout
+    layout_info: Cow<'static, [[LayoutInfo; 2]]>,
+    // one for each of SET_SIZES
+    sheets: Vec<PieceSheet>,
+}
+
+#[derive(Clone)]
+struct PieceSheet {
+    rows: i32,
+    cols: i32,
+    // events of each variant, at row * cols + col
+    events: Vec<Cow<'static, [SetPieceFeature]>>,
+    // the tile layers of the whole sheet for exported maps, `width` tiles per row
+    width: usize,
+    layers: Vec<(Cow<'static, str>, Cow<'static, [u32]>)>,
+}
+
+fn from_bundle(bundle: &'static Bundle) -> DataPack {
+    DataPack {
+        layouts: Cow::Borrowed(bundle.layouts),
+        layout_info: Cow::Borrowed(bundle.layout_info),
+        sheets: bundle
+            .set_pieces
+            .iter()
+            .zip(&bundle.tile_layers)
+            .map(|(&(rows, cols, events), &(width, layers))| PieceSheet {
+                rows,
+                cols,
+                events: events.iter().map(|&x| Cow::Borrowed(x)).collect(),
+                width,
+                layers: layers
+                    .iter()
+                    .map(|&(name, gids)| (Cow::Borrowed(name), Cow::Borrowed(gids)))
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+// one for each bundle in map_data::BUNDLES
+fn builtin_packs() -> &'static [Arc<DataPack>] {
+    static BUILTIN: OnceLock<Vec<Arc<DataPack>>> = OnceLock::new();
+    BUILTIN.get_or_init(|| {
+        map_data::BUNDLES
+            .iter()
+            .map(|x| Arc::new(from_bundle(x)))
+            .collect()
+    })
+}
+
+// the newest bundle that's not newer than the game. a newer version's maps can differ, so
+// there's no falling back to those
+fn bundle_index(settings: &GameSettings) -> Option<usize> {
+    let version = settings.game_version();
+    map_data::BUNDLES.iter().rposition(|x| x.version <= version)
+}
+
+impl Default for DataPack {
+    fn default() -> Self {
+        DataPack::builtin()
+    }
+}
+
+static ACTIVE: RwLock<Option<Arc<DataPack>>> = RwLock::new(None);
+
+/// The pack that was set with `use_data_pack`, or the built-in data of the newest version.
+pub fn active_data_pack() -> Arc<DataPack> {
+    match &*ACTIVE.read().unwrap() {
+        Some(pack) => pack.clone(),
+        None => builtin_packs().last().unwrap().clone(),
+    }
+}
+
+/// The pack to predict with for these settings: the one set with `use_data_pack`, or else the
+/// built-in data for the game version, if there is any.
+pub fn data_pack_for(settings: &GameSettings) -> Result<Arc<DataPack>, DataPackError> {
+    match &*ACTIVE.read().unwrap() {
+        Some(pack) => Ok(pack.clone()),
+        None => match bundle_index(settings) {
+            Some(index) => Ok(builtin_packs()[index].clone()),
+            None => Err(DataPackError::NoMapData {
+                version: settings.game_version(),
+            }),
+        },
+    }
+}
+
+/// Make all predictions from now on with `pack` instead of the built-in data.
+#[wasm_bindgen]
+pub fn use_data_pack(pack: &DataPack) {
+    *ACTIVE.write().unwrap() = Some(Arc::new(pack.clone()));
+}
+
+#[wasm_bindgen]
+pub fn use_builtin_data() {
+    *ACTIVE.write().unwrap() = None;
+}
+
+impl LayoutAnomaly {
+    /// What's odd about the set piece and what the game does about it, in `lang`. Unlike the
+    /// `Display` text, this doesn't say which layout it's in.
+    pub fn describe(&self, lang: Language) -> String {
+        let n = |x: i32| x.to_string();
+        let problem = match self.kind {
+            AnomalyKind::OddSize { found, size } => i18n::tr_args(
+                lang,
+                if found > size {
+                    "anomaly.too_big"
+                } else {
+                    "anomaly.too_small"
+                },
+                &[("found", &n(found)), ("size", &n(size))],
+            ),
+            AnomalyKind::Overlap { x, y } => i18n::tr_args(
+                lang,
+                "anomaly.overlap",
+                &[("other_x", &n(x)), ("other_y", &n(y))],
+            ),
+            AnomalyKind::CutOffByEdge { found } => {
+                i18n::tr_args(lang, "anomaly.cut_off", &[("found", &n(found))])
+            }
+            AnomalyKind::PastEdge { size } => {
+                i18n::tr_args(lang, "anomaly.past_edge", &[("size", &n(size))])
+            }
+        };
+        i18n::tr_args(
+            lang,
+            "anomaly.set_piece",
+            &[("x", &n(self.x)), ("y", &n(self.y)), ("problem", &problem)],
+        )
+    }
+}
+
+impl Display for LayoutAnomaly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "layout {}", self.layout)?;
+        if self.flip_x {
+            write!(f, " (flipped)")?;
+        }
+        write!(f, ", {}", self.describe(Language::English))
+    }
+}
+
+/// Everything odd about the layouts of the active pack, explained.
+#[wasm_bindgen]
+pub fn validate_layouts() -> js_sys::Array {
+    active_data_pack()
+        .validate()
+        .into_iter()
+        .map(|x| JsValue::from(x.to_string()))
+        .collect()
+}
+
+// (name, gids) of a tile layer
+type TileLayer = (String, Vec<u32>);
+
+// (width, height, layers) of the tile layers of a set piece sheet, the gids moved so the first
+// tileset starts at 1 like in the built-in sheets
+fn parse_tile_layers(tmx: &str) -> Result<(i32, i32, Vec<TileLayer>), String> {
+    let doc = roxmltree::Document::parse(tmx).map_err(|e| e.to_string())?;
+    let root = doc.root_element();
+    let firstgid: u32 = root
+        .children()
+        .find(|x| x.has_tag_name("tileset"))
+        .and_then(|x| x.attribute("firstgid"))
+        .unwrap_or("1")
+        .parse()
+        .map_err(|_| "bad tileset firstgid".to_string())?;
+    let mut size = None;
+    let mut layers = vec![];
+    for layer in root.children().filter(|x| x.has_tag_name("layer")) {
+        let name = layer.attribute("name").unwrap_or("");
+        let dimension = |dim| {
+            layer
+                .attribute(dim)
+                .and_then(|x| x.parse::<i32>().ok())
+                .ok_or(format!("{} layer has no valid {}", name, dim))
+        };
+        let (width, height) = (dimension("width")?, dimension("height")?);
+        if *size.get_or_insert((width, height)) != (width, height) {
+            return Err(format!("{} layer isn't the size of the others", name));
+        }
+        let data = layer
+            .children()
+            .find(|x| x.has_tag_name("data"))
+            .ok_or(format!("{} layer has no data", name))?;
+        if data.attribute("encoding") != Some("csv") {
+            return Err("only csv encoded layers are supported".to_string());
+        }
+        let gids = data
+            .text()
+            .unwrap_or("")
+            .split(',')
+            .map(|x| {
+                let gid: u32 = x
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("bad tile {:?}", x.trim()))?;
+                let tile = gid & !FLIP_FLAGS;
+                Ok(if tile == 0 {
+                    0
+                } else {
+                    (tile.saturating_sub(firstgid) + 1) | (gid & FLIP_FLAGS)
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        if gids.len() != (width * height) as usize {
+            return Err(format!(
+                "{} layer has {} tiles, expected {}",
+                name,
+                gids.len(),
+                width * height
+            ));
+        }
+        layers.push((name.to_string(), gids));
+    }
+    let (width, height) = size.ok_or("no tile layers")?;
+    Ok((width, height, layers))
+}
+
+impl DataPack {
+    /// The built-in data of the newest game version.
+    pub fn builtin() -> DataPack {
+        builtin_packs().last().unwrap().as_ref().clone()
+    }
+
+    /// Replace the layouts with the ones from a `Layouts.png`.
+    pub fn set_layouts(&mut self, png_data: &[u8]) -> Result<(), DataPackError> {
+        let layouts = game_data::decode_layouts(png_data).map_err(DataPackError::Layouts)?;
+        if layouts.len() < NUM_LAYOUTS * LAYOUT_SIZE {
+            return Err(DataPackError::Layouts(format!(
+                "has {} layouts, the game needs at least {}",
+                layouts.len() / LAYOUT_SIZE,
+                NUM_LAYOUTS
+            )));
+        }
+        self.layout_info = layouts
+            .chunks(LAYOUT_SIZE)
+            .map(|tiles| {
+                [
+                    game_data::layout_info(tiles, false),
+                    game_data::layout_info(tiles, true),
+                ]
+            })
+            .collect();
+        self.layouts = Cow::Owned(game_data::pack_tiles(&layouts));
+        Ok(())
+    }
+
+    /// Replace the set pieces of size `set_size` with the ones from a `Volcano_SetPieces_*.tmx`.
+    pub fn set_set_pieces(&mut self, set_size: i32, tmx: &str) -> Result<(), DataPackError> {
+        let err = |message| DataPackError::SetPieces {
+            size: set_size,
+            message,
+        };
+        let Some(index) = set_size_index(set_size) else {
+            return Err(err(format!(
+                "the game has no set pieces of size {}",
+                set_size
+            )));
+        };
+        let (width, height, layers) = parse_tile_layers(tmx).map_err(err)?;
+        let paths = &layers
+            .iter()
+            .find(|x| x.0 == "Paths")
+            .ok_or_else(|| err("no Paths layer".to_string()))?
+            .1;
+        let (num_rows, num_cols) = (height / set_size, width / set_size);
+        if num_rows == 0 || num_cols == 0 {
+            return Err(err("too small to hold a single set piece".to_string()));
+        }
+        let events = game_data::piece_events(set_size, num_rows, num_cols, |x, y| {
+            if x < width && y < height {
+                let gid = paths[(y * width + x) as usize] & !FLIP_FLAGS;
+                gid.checked_sub(1)
+            } else {
+                None
+            }
+        })
+        .map_err(err)?;
+        self.sheets[index] = PieceSheet {
+            rows: num_rows,
+            cols: num_cols,
+            events: events.into_iter().map(Cow::Owned).collect(),
+            width: width as usize,
+            layers: layers
+                .into_iter()
+                .map(|(name, gids)| (Cow::Owned(name), Cow::Owned(gids)))
+                .collect(),
+        };
+        Ok(())
+    }
+
+    /// Load whatever of `Layouts.png` and `Volcano_SetPieces_*.tmx` is in `dir`, on top of the
+    /// built-in data.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn load_dir(dir: &std::path::Path) -> Result<DataPack, DataPackError> {
+        let mut pack = DataPack::builtin();
+        let layouts = dir.join("Layouts.png");
+        if layouts.exists() {
+            pack.set_layouts(&std::fs::read(layouts)?)?;
+        }
+        for set_size in SET_SIZES {
+            let path = dir.join(format!("Volcano_SetPieces_{}.tmx", set_size));
+            if path.exists() {
+                pack.set_set_pieces(set_size, &std::fs::read_to_string(path)?)?;
+            }
+        }
+        Ok(pack)
+    }
+
+    // packed, use map_data::unpack_tiles
+    pub(crate) fn layout(&self, layout_id: u32) -> &[u8] {
+        let start = PACKED_LAYOUT_SIZE * layout_id as usize;
+        &self.layouts[start..start + PACKED_LAYOUT_SIZE]
+    }
+
+    /// Where the set pieces, stairs, buttons and monster spawns are on a layout.
+    pub fn layout_info(&self, layout_id: u32, flip_x: bool) -> &LayoutInfo {
+        &self.layout_info[layout_id as usize][flip_x as usize]
+    }
+
+    /// Every set piece square in the layouts that the game's scan treats oddly.
+    pub fn validate(&self) -> Vec<LayoutAnomaly> {
+        let mut out = vec![];
+        for (layout, infos) in self.layout_info.iter().enumerate() {
+            for (flip_x, info) in [false, true].into_iter().zip(infos) {
+                out.extend(game_data::validate_layout(layout as u32, flip_x, info));
+            }
+        }
+        out
+    }
+
+    /// The names of the tile layers in the set piece sheets, in the order of the first sheet
+    /// that has them.
+    pub(crate) fn tile_layer_names(&self) -> Vec<&str> {
+        let mut out: Vec<&str> = vec![];
+        for (name, _) in self.sheets.iter().flat_map(|x| &x.layers) {
+            if !out.contains(&&name[..]) {
+                out.push(name);
+            }
+        }
+        out
+    }
+
+    // (sheet width, gids) of one tile layer of a set piece sheet, if it has that layer
+    pub(crate) fn tile_layer(&self, set_size: i32, name: &str) -> Option<(usize, &[u32])> {
+        let sheet = &self.sheets[set_size_index(set_size)?];
+        let (_, gids) = sheet.layers.iter().find(|x| x.0 == name)?;
+        Some((sheet.width, gids))
+    }
+
+    fn sheet(&self, set_size: i32) -> &PieceSheet {
+        &self.sheets[set_size_index(set_size).expect("invalid set size")]
+    }
+
+    // (rows, cols)
+    pub(crate) fn piece_sizes(&self, set_size: i32) -> (i32, i32) {
+        let sheet = self.sheet(set_size);
+        (sheet.rows, sheet.cols)
+    }
+
+    pub(crate) fn piece_events(&self, set_size: i32, row: i32, col: i32) -> &[SetPieceFeature] {
+        let sheet = self.sheet(set_size);
+        &sheet.events[(row * sheet.cols + col) as usize]
+    }
+}
+
+#[wasm_bindgen]
+impl DataPack {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> DataPack {
+        DataPack::builtin()
+    }
+
+    #[wasm_bindgen(js_name = set_layouts)]
+    pub fn js_set_layouts(&mut self, png_data: &[u8]) -> Result<(), JsError> {
+        Ok(self.set_layouts(png_data)?)
+    }
+
+    #[wasm_bindgen(js_name = set_set_pieces)]
+    pub fn js_set_set_pieces(&mut self, set_size: i32, tmx: &str) -> Result<(), JsError> {
+        Ok(self.set_set_pieces(set_size, tmx)?)
+    }
+
+    pub fn layout_count(&self) -> usize {
+        self.layouts.len() / PACKED_LAYOUT_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the build used to warn about these, so a changed layout or scan shows up here
+    #[test]
+    fn builtin_layout_anomalies() {
+        use AnomalyKind::*;
+        let found: Vec<_> = DataPack::builtin()
+            .validate()
+            .into_iter()
+            .map(|x| (x.layout, x.flip_x, x.x, x.y, x.kind))
+            .collect();
+        let odd = |found, size| OddSize { found, size };
+        let overlap = |x, y| Overlap { x, y };
+        assert_eq!(
+            found,
+            [
+                (2, false, 23, 31, odd(5, 4)),
+                (2, false, 35, 35, odd(5, 4)),
+                (2, true, 24, 35, odd(5, 4)),
+                (2, true, 36, 31, odd(5, 4)),
+                (40, false, 31, 9, odd(2, 3)),
+                (40, false, 32, 11, odd(1, 3)),
+                (40, false, 32, 11, overlap(31, 9)),
+                (40, false, 33, 9, odd(1, 3)),
+                (40, false, 33, 9, overlap(31, 9)),
+                (40, false, 33, 9, overlap(32, 11)),
+                (40, false, 33, 10, odd(1, 3)),
+                (40, false, 33, 10, overlap(31, 9)),
+                (40, false, 33, 10, overlap(32, 11)),
+                (40, false, 33, 10, overlap(33, 9)),
+                (40, false, 33, 11, odd(1, 3)),
+                (40, false, 33, 11, overlap(31, 9)),
+                (40, false, 33, 11, overlap(32, 11)),
+                (40, false, 33, 11, overlap(33, 9)),
+                (40, false, 33, 11, overlap(33, 10)),
+                (40, false, 35, 13, overlap(33, 11)),
+                (41, false, 17, 27, odd(1, 3)),
+                (41, false, 18, 25, overlap(17, 27)),
+                (41, true, 46, 27, odd(1, 3)),
+            ]
+        );
+    }
+
+    // a layout of wall with set piece rectangles (x, y, width, height) drawn in
+    fn anomalies(pieces: &[(i32, i32, i32, i32)], flip_x: bool) -> Vec<(i32, i32, AnomalyKind)> {
+        let mut tiles = vec![crate::MapTile::Wall as u8; LAYOUT_SIZE];
+        for &(x, y, w, h) in pieces {
+            for y in y..y + h {
+                for x in x..x + w {
+                    tiles[(y * 64 + x) as usize] = crate::MapTile::SetPiece as u8;
+                }
+            }
+        }
+        let info = game_data::layout_info(&tiles, flip_x);
+        game_data::validate_layout(7, flip_x, &info)
+            .into_iter()
+            .map(|x| {
+                assert_eq!((x.layout, x.flip_x), (7, flip_x));
+                (x.x, x.y, x.kind)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn valid_squares() {
+        let pieces = [
+            (2, 2, 3, 3),
+            (10, 2, 4, 4),
+            (20, 2, 8, 8),
+            (2, 20, 16, 16),
+            (30, 30, 32, 32),
+        ];
+        assert_eq!(anomalies(&pieces, false), []);
+        assert_eq!(anomalies(&[(5, 5, 3, 3)], true), []);
+    }
+
+    #[test]
+    fn odd_size() {
+        use AnomalyKind::*;
+        // the game rounds down to 4, the last row and column are just floor
+        assert_eq!(
+            anomalies(&[(10, 10, 5, 5)], false),
+            [(10, 10, OddSize { found: 5, size: 4 })]
+        );
+        // a 6x3 strip is two 3x3 pieces side by side
+        assert_eq!(anomalies(&[(10, 10, 6, 3)], false), []);
+        assert_eq!(
+            anomalies(&[(10, 10, 9, 9)], true),
+            [(45, 10, OddSize { found: 9, size: 8 })]
+        );
+    }
+
+    #[test]
+    fn overlap() {
+        use AnomalyKind::*;
+        // a 2x2 square still gets a 3x3 piece, running into the square next to it
+        assert_eq!(
+            anomalies(&[(10, 10, 2, 2), (12, 10, 3, 3)], false),
+            [
+                (10, 10, OddSize { found: 2, size: 3 }),
+                (12, 10, Overlap { x: 10, y: 10 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn cut_off_by_edge() {
+        use AnomalyKind::*;
+        assert_eq!(
+            anomalies(&[(61, 20, 3, 3)], false),
+            [(61, 20, CutOffByEdge { found: 3 })]
+        );
+        assert_eq!(
+            anomalies(&[(20, 59, 5, 5)], false),
+            [
+                (20, 59, OddSize { found: 5, size: 4 }),
+                (20, 59, CutOffByEdge { found: 5 }),
+            ]
+        );
+        assert_eq!(
+            anomalies(&[(0, 20, 3, 3)], true),
+            [(61, 20, CutOffByEdge { found: 3 })]
+        );
+    }
+
+    #[test]
+    fn past_edge() {
+        use AnomalyKind::*;
+        assert_eq!(
+            anomalies(&[(62, 10, 2, 2)], false),
+            [
+                (62, 10, OddSize { found: 2, size: 3 }),
+                (62, 10, CutOffByEdge { found: 2 }),
+                (62, 10, PastEdge { size: 3 }),
+            ]
+        );
+    }
+}
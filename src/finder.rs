@@ -0,0 +1,248 @@
+use std::fmt::Display;
+
+use wasm_bindgen::prelude::*;
+
+use crate::narrow::push_range;
+use crate::{
+    display_luck, do_dungeon, format_date, i18n, is_mushroom_floor, CommonChest, DataPackError,
+    DungeonPrediction, GameSettings, Goodie, Language, LuckSet, RareChest,
+};
+
+/// What makes a day worth a trip to the volcano. It's checked for one luck value at a time,
+/// since that's all the player gets on a day.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DayCondition {
+    /// a rare chest on any floor
+    AnyRareChest,
+    /// a chest with this item in it, by qualified item id, e.g. "(O)863" for the phoenix ring
+    Item(String),
+    /// a mushroom floor on the way down
+    MushroomFloor,
+    /// at least this many dragon teeth, over all floors
+    DragonTeeth(usize),
+}
+
+impl DayCondition {
+    fn holds(&self, layouts: &[u32; 10], loots: &[&[Goodie]; 10]) -> bool {
+        let mut goodies = loots.iter().flat_map(|x| x.iter());
+        match self {
+            DayCondition::AnyRareChest => goodies.any(|x| matches!(x, Goodie::RareChest(_))),
+            DayCondition::Item(id) => goodies.any(|x| match x {
+                Goodie::CommonChest(c) => c.item_id() == id,
+                Goodie::RareChest(c) => c.item_id() == id,
+                _ => false,
+            }),
+            DayCondition::MushroomFloor => layouts.iter().any(|&x| is_mushroom_floor(x)),
+            DayCondition::DragonTeeth(count) => {
+                goodies.filter(|x| **x == Goodie::DragonTooth).count() >= *count
+            }
+        }
+    }
+}
+
+/// A day on which the condition can be met.
+#[derive(Clone, Debug)]
+pub struct GoodDay {
+    pub day: u32,
+    /// e.g. "summer 3, Y1"
+    pub date: String,
+    /// the luck it's met at, in luckMult units like `ProbabilityRange`
+    pub luck: LuckSet,
+}
+
+impl Display for GoodDay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (day {}), luck ", self.date, self.day)?;
+        let ranges: Vec<String> = self
+            .luck
+            .iter()
+            .map(|&(a, b)| format!("{:.4} to {:.4}", display_luck(a), display_luck(b)))
+            .collect();
+        f.write_str(&ranges.join(" or "))
+    }
+}
+
+// the luck at which `condition` is met on the predicted day
+fn matching_luck(prediction: &DungeonPrediction, condition: &DayCondition) -> LuckSet {
+    // every floor's ranges cover the same luck, and between two of these starts nothing
+    // changes on any floor
+    let mut starts: Vec<f64> = prediction
+        .layouts
+        .iter()
+        .flat_map(|x| x.iter().map(|x| x.0))
+        .chain(prediction.loots.iter().flat_map(|x| x.iter().map(|x| x.0)))
+        .collect();
+    starts.sort_by(|a, b| a.total_cmp(b));
+    starts.dedup();
+    let mut out = vec![];
+    for start in starts {
+        let mut end = f64::INFINITY;
+        let layouts = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9].map(|lvl| {
+            let range = prediction.layouts[lvl]
+                .iter()
+                .find(|x| x.0 <= start && start <= x.1)
+                .expect("floors should cover the same luck");
+            end = end.min(range.1);
+            range.2
+        });
+        let loots = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9].map(|lvl| {
+            let range = prediction.loots[lvl]
+                .iter()
+                .find(|x| x.0 <= start && start <= x.1)
+                .expect("floors should cover the same luck");
+            end = end.min(range.1);
+            &range.2[..]
+        });
+        if condition.holds(&layouts, &loots) {
+            push_range(&mut out, start, end);
+        }
+    }
+    out
+}
+
+/// The first `count` days from `settings.days_played` up to `last_day` on which `condition`
+/// can be met with the player's luck.
+pub fn find_good_days(
+    settings: GameSettings,
+    condition: &DayCondition,
+    count: usize,
+    last_day: u32,
+) -> Result<Vec<GoodDay>, DataPackError> {
+    let mut out = vec![];
+    for day in settings.days_played.max(1)..=last_day {
+        if out.len() >= count {
+            break;
+        }
+        let prediction = do_dungeon(GameSettings {
+            days_played: day,
+            ..settings
+        })?;
+        let luck = matching_luck(&prediction, condition);
+        if !luck.is_empty() {
+            out.push(GoodDay {
+                day,
+                date: format_date(day),
+                luck,
+            });
+        }
+    }
+    Ok(out)
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DayConditionKind {
+    AnyRareChest,
+    Item,
+    MushroomFloor,
+    DragonTeeth,
+}
+
+/// `find_good_days` over the next `num_days` days. `item` is only used for `Item` and `teeth`
+/// only for `DragonTeeth`. Returns one line of text per day.
+#[wasm_bindgen]
+pub fn good_days(
+    settings: GameSettings,
+    kind: DayConditionKind,
+    item: String,
+    teeth: u32,
+    count: u32,
+    num_days: u32,
+) -> Result<js_sys::Array, JsError> {
+    let condition = match kind {
+        DayConditionKind::AnyRareChest => DayCondition::AnyRareChest,
+        DayConditionKind::Item => DayCondition::Item(item),
+        DayConditionKind::MushroomFloor => DayCondition::MushroomFloor,
+        DayConditionKind::DragonTeeth => DayCondition::DragonTeeth(teeth as usize),
+    };
+    let last_day = settings
+        .days_played
+        .max(1)
+        .saturating_add(num_days.max(1) - 1);
+    Ok(
+        find_good_days(settings, &condition, count as usize, last_day)?
+            .into_iter()
+            .map(|x| JsValue::from(x.to_string()))
+            .collect(),
+    )
+}
+
+/// Every item a chest can have, as [qualified item id, name] pairs.
+#[wasm_bindgen]
+pub fn chest_items(lang: Language) -> js_sys::Array {
+    let mut ids = vec![];
+    for item in CommonChest::ITEMS.iter().chain(RareChest::ITEMS) {
+        if !ids.contains(&item.id) {
+            ids.push(item.id);
+        }
+    }
+    ids.into_iter()
+        .map(|id| {
+            let pair: js_sys::Array = [id, i18n::tr(lang, id)]
+                .into_iter()
+                .map(JsValue::from)
+                .collect();
+            JsValue::from(pair)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHOENIX_RING: &str = "(O)863";
+
+    fn settings(days_played: u32) -> GameSettings {
+        GameSettings {
+            seed: 123456789,
+            days_played,
+            max_luck_lvl: 2,
+            ..GameSettings::new()
+        }
+    }
+
+    #[test]
+    fn luck_on_one_day() {
+        // floor 2 is the mushroom floor 32 from the first split on, floor 4's chest is rare
+        // from the second
+        let prediction = do_dungeon(settings(22)).unwrap();
+        let luck = |condition| matching_luck(&prediction, &condition);
+        assert_eq!(
+            luck(DayCondition::Item(PHOENIX_RING.to_string())),
+            [(1.0641154755671118, 1.12)]
+        );
+        assert_eq!(
+            luck(DayCondition::MushroomFloor),
+            [(0.9687672075670061, 1.12)]
+        );
+        assert_eq!(luck(DayCondition::DragonTeeth(3)), [(0.95, 1.12)]);
+        assert_eq!(luck(DayCondition::DragonTeeth(100)), []);
+        assert_eq!(luck(DayCondition::Item("(O)289".to_string())), []);
+    }
+
+    #[test]
+    fn good_days_in_range() {
+        let condition = DayCondition::Item(PHOENIX_RING.to_string());
+        let days = |first, count, last_day| -> Vec<u32> {
+            find_good_days(settings(first), &condition, count, last_day)
+                .unwrap()
+                .iter()
+                .map(|x| x.day)
+                .collect()
+        };
+        // no phoenix ring on days 15 to 20
+        assert_eq!(days(15, 3, 60), [21, 22, 24]);
+        // last_day is included, and ends the search before `count` is reached
+        assert_eq!(days(15, 3, 23), [21, 22]);
+        assert_eq!(days(15, 3, 21), [21]);
+        assert_eq!(days(15, 3, 20), []);
+        assert_eq!(days(22, 1, 60), [22]);
+
+        let found = find_good_days(settings(22), &condition, 1, 22).unwrap();
+        assert_eq!(
+            found[0].to_string(),
+            "spring 22, Y1 (day 22), luck 0.1282 to 0.2400"
+        );
+    }
+}
@@ -0,0 +1,286 @@
+// parsing of the game's map files. this is included both by build.rs, for the built-in data,
+// and by the crate, for loading modded data at runtime, so it can't depend on anything but
+// `png` and the `MapTile`/`SetPieceFeature` definitions of whoever includes it.
+
+use std::borrow::Cow;
+
+use super::MapTile;
+
+// every set piece size the game has a sheet for
+pub const SET_SIZES: [i32; 5] = [3, 4, 8, 16, 32];
+
+// bytes per layout once packed with `pack_tiles`
+pub const PACKED_LAYOUT_SIZE: usize = 64 * 64 * 3 / 8;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SetPieceFeature {
+    Rng,
+    /// with the tile it's on, from the set piece's top left corner
+    Tooth {
+        x: i32,
+        y: i32,
+    },
+    Chest {
+        x: i32,
+        y: i32,
+    },
+}
+
+/// Decode `Layouts.png` into one byte (a `MapTile`) per tile, 64x64 tiles per layout, layouts in
+/// row major order. Trailing layouts that are all wall are dropped.
+pub fn decode_layouts(png_data: &[u8]) -> Result<Vec<u8>, String> {
+    let decoder = png::Decoder::new(png_data);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|e| e.to_string())?;
+    if info.color_type != png::ColorType::Rgba {
+        return Err(format!("unexpected color type {:?}", info.color_type));
+    }
+    if info.bit_depth != png::BitDepth::Eight {
+        return Err(format!("unexpected bit depth {:?}", info.bit_depth));
+    }
+    let layout_rows = info.height as usize / 64;
+    let layout_cols = info.width as usize / 64;
+    let mut layouts = Vec::new();
+    for layout_y in 0..layout_rows {
+        for layout_x in 0..layout_cols {
+            for y in 0..64 {
+                for x in 0..64 {
+                    let px_x = layout_x * 64 + x;
+                    let px_y = layout_y * 64 + y;
+                    let px_offset = info.line_size * px_y + 4 * px_x;
+                    let color = &buf[px_offset..px_offset + 3];
+                    let tile = match color {
+                        [255, 0, 0] => MapTile::Exit,
+                        [0, 255, 0] => MapTile::Enter,
+                        [0, 0, 255] => MapTile::Lava,
+                        [255, 255, 0] => MapTile::SetPiece,
+                        [128, 128, 128] => MapTile::SwitchLocation,
+                        [0, 255, 255] => MapTile::MonsterSpawn,
+                        [0, 0, 0] => MapTile::Wall,
+                        [255, 255, 255] => MapTile::Floor,
+                        _ => {
+                            return Err(format!(
+                                "unexpected color {:?} at x={} y={}",
+                                color, px_x, px_y
+                            ))
+                        }
+                    };
+                    layouts.push(tile as u8);
+                }
+            }
+        }
+    }
+    while layouts.len() >= 64 * 64
+        && layouts[layouts.len() - 64 * 64..]
+            .iter()
+            .all(|&x| x == MapTile::Wall as u8)
+    {
+        layouts.truncate(layouts.len() - 64 * 64);
+    }
+    Ok(layouts)
+}
+
+/// Pack tiles from `decode_layouts` into 3 bits each, 8 tiles in every 3 bytes, lowest bits
+/// first.
+pub fn pack_tiles(tiles: &[u8]) -> Vec<u8> {
+    tiles
+        .chunks(8)
+        .flat_map(|group| {
+            let bits = group
+                .iter()
+                .enumerate()
+                .fold(0_u32, |acc, (i, &tile)| acc | (tile as u32) << (3 * i));
+            let [a, b, c, _] = bits.to_le_bytes();
+            [a, b, c]
+        })
+        .collect()
+}
+
+/// Where things are on a layout, as the game finds them when it loads the floor.
+#[derive(Clone, Debug)]
+pub struct LayoutInfo {
+    /// (x, y, size as drawn, size the game uses) of every set piece, in the order the game
+    /// places them. sizes that aren't a valid set size get rounded down to one.
+    pub set_pieces: Cow<'static, [(i32, i32, i32, i32)]>,
+    pub entrances: Cow<'static, [(i32, i32)]>,
+    pub exits: Cow<'static, [(i32, i32)]>,
+    pub switch_locations: Cow<'static, [(i32, i32)]>,
+    pub monster_spawns: Cow<'static, [(i32, i32)]>,
+}
+
+/// Scan one layout (64x64 bytes from `decode_layouts`), mirrored horizontally if `flip_x`.
+pub fn layout_info(tiles: &[u8], flip_x: bool) -> LayoutInfo {
+    let mut map: Vec<u8> = tiles.to_vec();
+    if flip_x {
+        for row in map.chunks_mut(64) {
+            row.reverse();
+        }
+    }
+    let at = |map: &[u8], x: i32, y: i32| map[(y * 64 + x) as usize];
+    let find = |tile: MapTile| -> Cow<'static, [(i32, i32)]> {
+        let found: Vec<_> = (0..64)
+            .flat_map(|y| (0..64).map(move |x| (x, y)))
+            .filter(|&(x, y)| at(&map, x, y) == tile as u8)
+            .collect();
+        Cow::Owned(found)
+    };
+    let entrances = find(MapTile::Enter);
+    let exits = find(MapTile::Exit);
+    let switch_locations = find(MapTile::SwitchLocation);
+    let monster_spawns = find(MapTile::MonsterSpawn);
+
+    // same scan as the game: columns first, each square found gets cleared so it's not found
+    // again. only the top and left edge decide how big the square is.
+    let set_piece = MapTile::SetPiece as u8;
+    let mut set_pieces = vec![];
+    for x in 0..64 {
+        for y in 0..64 {
+            if at(&map, x, y) != set_piece {
+                continue;
+            }
+            let mut j = 0;
+            while x + j < 64
+                && y + j < 64
+                && at(&map, x + j, y) == set_piece
+                && at(&map, x, y + j) == set_piece
+            {
+                j += 1;
+            }
+            for y in y..y + j {
+                for x in x..x + j {
+                    map[(y * 64 + x) as usize] = MapTile::Floor as u8;
+                }
+            }
+            let size = match j {
+                32.. => 32,
+                16.. => 16,
+                8.. => 8,
+                4.. => 4,
+                _ => 3,
+            };
+            set_pieces.push((x, y, j, size));
+        }
+    }
+    LayoutInfo {
+        set_pieces: Cow::Owned(set_pieces),
+        entrances,
+        exits,
+        switch_locations,
+        monster_spawns,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnomalyKind {
+    /// the square isn't one of the set piece sizes
+    OddSize { found: i32, size: i32 },
+    /// the square the game uses overlaps the one of an earlier set piece at (x, y)
+    Overlap { x: i32, y: i32 },
+    /// the scan ran into the edge of the map while growing the square
+    CutOffByEdge { found: i32 },
+    /// the square the game uses reaches outside the map
+    PastEdge { size: i32 },
+}
+
+/// Something odd about a set piece square in a layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LayoutAnomaly {
+    pub layout: u32,
+    pub flip_x: bool,
+    pub x: i32,
+    pub y: i32,
+    pub kind: AnomalyKind,
+}
+
+/// Check the set pieces the game finds on a layout for anything that doesn't look like a
+/// deliberate square of a valid size.
+pub fn validate_layout(layout: u32, flip_x: bool, info: &LayoutInfo) -> Vec<LayoutAnomaly> {
+    let mut out = vec![];
+    let mut add = |x, y, kind| {
+        out.push(LayoutAnomaly {
+            layout,
+            flip_x,
+            x,
+            y,
+            kind,
+        })
+    };
+    for (i, &(x, y, found, size)) in info.set_pieces.iter().enumerate() {
+        if found != size {
+            add(x, y, AnomalyKind::OddSize { found, size });
+        }
+        if x + found == 64 || y + found == 64 {
+            add(x, y, AnomalyKind::CutOffByEdge { found });
+        }
+        if x + size > 64 || y + size > 64 {
+            add(x, y, AnomalyKind::PastEdge { size });
+        }
+        for &(ox, oy, _, osize) in &info.set_pieces[..i] {
+            if x < ox + osize && ox < x + size && y < oy + osize && oy < y + size {
+                add(x, y, AnomalyKind::Overlap { x: ox, y: oy });
+            }
+        }
+    }
+    out
+}
+
+/// Work out the events of every variant in a set piece sheet from its "Paths" layer. `get_tile`
+/// returns the tile index (not gid) at a position, or `None` for empty or out of bounds tiles.
+/// The result is indexed by `row * num_cols + col`.
+pub fn piece_events(
+    set_size: i32,
+    num_rows: i32,
+    num_cols: i32,
+    get_tile: impl Fn(i32, i32) -> Option<u32>,
+) -> Result<Vec<Vec<SetPieceFeature>>, String> {
+    let mut out = vec![];
+    for selected_row in 0..num_rows {
+        for selected_col in 0..num_cols {
+            let mut events = vec![];
+            for setx in 0..set_size {
+                // this really shouldn't be ..=, but that's what the game does
+                for sety in 0..=set_size {
+                    let src_x = selected_col * set_size + setx;
+                    let src_y = selected_row * set_size + sety;
+                    let Some(tile) = get_tile(src_x, src_y) else {
+                        continue;
+                    };
+                    match tile {
+                        234..=239 => {
+                            // possible gate location, random
+                            events.push(SetPieceFeature::Rng);
+                        }
+                        250 => return Err("set piece contained switch for gate #0".to_string()),
+                        // possible setpiece switch location - not worth to track
+                        251..=255 => {}
+                        330 => {}
+                        331 => {}
+                        332 => {
+                            events.push(SetPieceFeature::Chest { x: setx, y: sety });
+                        }
+                        // wall - not even used ingame??
+                        333 => {}
+                        334 => {
+                            // barrel
+                            events.push(SetPieceFeature::Rng);
+                        }
+                        335 => {
+                            events.push(SetPieceFeature::Tooth { x: setx, y: sety });
+                        }
+                        // spiker spawn point
+                        346 => {}
+                        _ => {
+                            return Err(format!(
+                                "unknown tile on path layer: {} at x={} y={}",
+                                tile, src_x, src_y
+                            ));
+                        }
+                    }
+                }
+            }
+            out.push(events);
+        }
+    }
+    Ok(out)
+}
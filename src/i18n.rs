@@ -0,0 +1,156 @@
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+    Spanish,
+    French,
+    Portuguese,
+    Chinese,
+    Japanese,
+    Korean,
+    Russian,
+    Italian,
+    Hungarian,
+    Turkish,
+}
+
+macro_rules! tables {
+    ($lang:expr, $dir:literal) => {
+        match $lang {
+            Language::English => include_str!(concat!($dir, "en.txt")),
+            Language::German => include_str!(concat!($dir, "de.txt")),
+            Language::Spanish => include_str!(concat!($dir, "es.txt")),
+            Language::French => include_str!(concat!($dir, "fr.txt")),
+            Language::Portuguese => include_str!(concat!($dir, "pt.txt")),
+            Language::Chinese => include_str!(concat!($dir, "zh.txt")),
+            Language::Japanese => include_str!(concat!($dir, "ja.txt")),
+            Language::Korean => include_str!(concat!($dir, "ko.txt")),
+            Language::Russian => include_str!(concat!($dir, "ru.txt")),
+            Language::Italian => include_str!(concat!($dir, "it.txt")),
+            Language::Hungarian => include_str!(concat!($dir, "hu.txt")),
+            Language::Turkish => include_str!(concat!($dir, "tr.txt")),
+        }
+    };
+}
+
+impl Language {
+    // "key = value" per line, # for comments. english has every key, the others can leave
+    // some out and fall back to it.
+    fn table(self) -> &'static str {
+        tables!(self, "lang/")
+    }
+
+    // item names by qualified item id, in the same format. these are the game's own names,
+    // examples/gen_lang.rs regenerates them from its strings
+    fn items(self) -> &'static str {
+        tables!(self, "lang/items/")
+    }
+}
+
+fn lookup(table: &'static str, key: &str) -> Option<&'static str> {
+    table
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .find(|(k, _)| k.trim() == key)
+        .map(|(_, v)| v.trim())
+}
+
+fn lookup_in(lang: Language, key: &str) -> Option<&'static str> {
+    if key.starts_with('(') {
+        lookup(lang.items(), key)
+    } else {
+        lookup(lang.table(), key)
+    }
+}
+
+/// The text for `key` (a qualified item id or one of our own keys) in `lang`, or the english
+/// one if that language doesn't have it. Unknown keys come back as they are.
+pub fn tr(lang: Language, key: &str) -> &str {
+    lookup_in(lang, key)
+        .or_else(|| lookup_in(Language::English, key))
+        .unwrap_or(key)
+}
+
+/// `tr` with `{name}` placeholders in the text filled in from `args`.
+pub fn tr_args(lang: Language, key: &str, args: &[(&str, &str)]) -> String {
+    let mut text = tr(lang, key).to_string();
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Language; 12] = [
+        Language::English,
+        Language::German,
+        Language::Spanish,
+        Language::French,
+        Language::Portuguese,
+        Language::Chinese,
+        Language::Japanese,
+        Language::Korean,
+        Language::Russian,
+        Language::Italian,
+        Language::Hungarian,
+        Language::Turkish,
+    ];
+
+    fn keys(table: &str) -> impl Iterator<Item = &str> {
+        table
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(k, _)| k.trim())
+    }
+
+    #[test]
+    fn every_key_has_english() {
+        for lang in ALL {
+            for key in keys(lang.table()).chain(keys(lang.items())) {
+                assert!(
+                    lookup_in(Language::English, key).is_some(),
+                    "{:?} has {} but english doesn't",
+                    lang,
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn items_are_translated() {
+        assert_ne!(
+            tr(Language::German, "(O)848"),
+            tr(Language::English, "(O)848")
+        );
+        for lang in ALL {
+            for key in keys(Language::English.items()) {
+                assert!(
+                    lookup(lang.items(), key).is_some(),
+                    "{:?} has no name for {}",
+                    lang,
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fallbacks() {
+        assert_eq!(tr(Language::English, "(O)852"), "Dragon Tooth");
+        assert_eq!(tr(Language::German, "no.such.key"), "no.such.key");
+        assert_eq!(
+            tr_args(Language::English, "goodies.floor", &[("floor", "3")]),
+            "floor 3:"
+        );
+    }
+}
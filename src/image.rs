@@ -0,0 +1,168 @@
+use std::io::Write;
+
+use crate::{floor_tiles, DataPackError, GameSettings, Tilemap};
+
+static TILES_8: &[u8] = include_bytes!("../icons/maptiles_8.png");
+static TILES_16: &[u8] = include_bytes!("../icons/maptiles_16.png");
+
+/// RGBA8 image, rows top to bottom.
+#[derive(Clone)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Image {
+            width,
+            height,
+            data: vec![0; width * height * 4],
+        }
+    }
+
+    pub fn decode_png(bytes: &[u8]) -> Result<Self, png::DecodingError> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+        let (width, height) = (info.width as usize, info.height as usize);
+        let mut data = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            let row = &buf[y * info.line_size..];
+            for x in 0..width {
+                let px: [u8; 4] = match info.color_type {
+                    png::ColorType::Rgba => row[x * 4..x * 4 + 4].try_into().unwrap(),
+                    png::ColorType::Rgb => [row[x * 3], row[x * 3 + 1], row[x * 3 + 2], 255],
+                    png::ColorType::GrayscaleAlpha => {
+                        let v = row[x * 2];
+                        [v, v, v, row[x * 2 + 1]]
+                    }
+                    png::ColorType::Grayscale => [row[x], row[x], row[x], 255],
+                    // expanded away by normalize_to_color8
+                    png::ColorType::Indexed => unreachable!(),
+                };
+                data.extend_from_slice(&px);
+            }
+        }
+        Ok(Image {
+            width,
+            height,
+            data,
+        })
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> [u8; 4] {
+        let off = (y * self.width + x) * 4;
+        self.data[off..off + 4].try_into().unwrap()
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, px: [u8; 4]) {
+        let off = (y * self.width + x) * 4;
+        self.data[off..off + 4].copy_from_slice(&px);
+    }
+
+    /// Copy a `w`x`h` rectangle from `src`, overwriting whatever was here (like canvas
+    /// `drawImage` onto a fresh canvas, the tiles are all opaque anyways).
+    #[allow(clippy::too_many_arguments)]
+    pub fn blit(
+        &mut self,
+        src: &Image,
+        sx: usize,
+        sy: usize,
+        w: usize,
+        h: usize,
+        dx: usize,
+        dy: usize,
+    ) {
+        for y in 0..h {
+            let src_off = ((sy + y) * src.width + sx) * 4;
+            let dst_off = ((dy + y) * self.width + dx) * 4;
+            self.data[dst_off..dst_off + w * 4]
+                .copy_from_slice(&src.data[src_off..src_off + w * 4]);
+        }
+    }
+
+    pub fn write_png<W: Write>(&self, w: W) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data)?;
+        writer.finish()
+    }
+
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut out = vec![];
+        // writing into a Vec can't fail
+        self.write_png(&mut out).unwrap();
+        out
+    }
+}
+
+/// The tile sizes there are tile sheets for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileSize {
+    Px8,
+    Px16,
+}
+
+impl TileSize {
+    pub fn pixels(self) -> usize {
+        match self {
+            TileSize::Px8 => 8,
+            TileSize::Px16 => 16,
+        }
+    }
+
+    pub fn from_pixels(pixels: usize) -> Option<Self> {
+        match pixels {
+            8 => Some(TileSize::Px8),
+            16 => Some(TileSize::Px16),
+            _ => None,
+        }
+    }
+}
+
+/// The same tile sheets that the web page draws from, decoded.
+pub fn tile_sheet(tile_sz: TileSize) -> Image {
+    let bytes = match tile_sz {
+        TileSize::Px8 => TILES_8,
+        TileSize::Px16 => TILES_16,
+    };
+    // these are built in, so they're known to decode
+    Image::decode_png(bytes).unwrap()
+}
+
+pub fn render_tilemap(tiles: &Tilemap, sheet: &Image) -> Image {
+    let tile_sz = sheet.height;
+    let mut out = Image::new(64 * tile_sz, 64 * tile_sz);
+    for y in 0..64 {
+        for x in 0..64 {
+            let tile_off = tile_sz * tiles[(x, y)] as u8 as usize;
+            out.blit(
+                sheet,
+                tile_off,
+                0,
+                tile_sz,
+                tile_sz,
+                x as usize * tile_sz,
+                y as usize * tile_sz,
+            );
+        }
+    }
+    out
+}
+
+/// Native counterpart of `render_map`: draw the floor into a PNG file.
+pub fn render_map_png(
+    settings: GameSettings,
+    lvl: i32,
+    layout: u32,
+    tile_sz: TileSize,
+) -> Result<Vec<u8>, DataPackError> {
+    let tiles = floor_tiles(settings, lvl, layout)?;
+    Ok(render_tilemap(&tiles, &tile_sheet(tile_sz)).to_png())
+}
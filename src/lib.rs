@@ -1,13 +1,54 @@
 use std::fmt::Write;
 use std::{
+    collections::HashMap,
     fmt::Display,
     ops::{Index, IndexMut},
+    sync::Arc,
 };
 use wasm_bindgen::prelude::*;
 use web_sys::{CanvasRenderingContext2d, HtmlImageElement};
 
+mod ansi;
+mod code;
+mod csv;
+mod data_pack;
+mod finder;
+mod game_data;
+mod i18n;
+mod image;
 mod map_data;
-mod rng;
+mod narrow;
+mod planner;
+mod report;
+pub mod rng;
+mod save;
+#[cfg(not(target_family = "wasm"))]
+mod search;
+mod svg;
+// the set piece tiles are only needed here, keep them out of the web build
+#[cfg(not(target_family = "wasm"))]
+mod tmx;
+
+pub use ansi::{render_floor_ansi, render_map_ansi};
+pub use code::CodeError;
+pub use csv::scan_days_csv;
+pub use data_pack::{
+    active_data_pack, data_pack_for, use_builtin_data, use_data_pack, validate_layouts, DataPack,
+    DataPackError,
+};
+pub use finder::{chest_items, find_good_days, good_days, DayCondition, DayConditionKind, GoodDay};
+pub use game_data::{AnomalyKind, LayoutAnomaly, LayoutInfo};
+pub use i18n::{tr, tr_args, Language};
+pub use image::{render_map_png, render_tilemap, tile_sheet, Image, TileSize};
+pub use narrow::{narrow_luck, LuckSet, Observation, Observations};
+pub use planner::{loot_items, plan_days, plan_trips, Plan, PlannedDay, TripPlanner};
+pub use report::{generate_report, ReportFormat};
+pub use save::{parse_save, SaveError};
+#[cfg(not(target_family = "wasm"))]
+pub use search::{search_days, search_seeds};
+pub use svg::{render_map_svg, render_overview_svg, render_overviews_svg};
+#[cfg(not(target_family = "wasm"))]
+pub use tmx::{export_tmx, floor_to_tmx};
 
 // this is part of stdlib in nightly
 fn f64_next_up(x: f64) -> f64 {
@@ -28,13 +69,25 @@ pub enum MapTile {
     MonsterSpawn = 7,
 }
 
+/// The game versions that generate the volcano differently.
 #[wasm_bindgen]
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GameVersion {
+    V1_5,
+    /// 1.6.0 to 1.6.3
+    #[default]
+    V1_6,
+    /// 1.6.4 and later
+    V1_6_4,
+}
+
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct GameSettings {
     pub seed: i32,
     pub legacy_rng: bool,
     pub has_caldera: bool,
-    pub post_1_6_4: bool,
+    pub version: GameVersion,
     pub cracked_golden_coconut: bool,
     pub special_charm: bool,
     pub days_played: u32,
@@ -48,8 +101,19 @@ impl GameSettings {
     }
 }
 
+impl GameSettings {
+    /// The oldest game version that these settings could be from.
+    pub fn game_version(&self) -> (u32, u32, u32) {
+        match self.version {
+            GameVersion::V1_5 => (1, 5, 0),
+            GameVersion::V1_6 => (1, 6, 0),
+            GameVersion::V1_6_4 => (1, 6, 4),
+        }
+    }
+}
+
 #[derive(Clone)]
-struct Tilemap([[MapTile; 64]; 64]);
+pub struct Tilemap([[MapTile; 64]; 64]);
 impl Index<(i32, i32)> for Tilemap {
     type Output = MapTile;
     fn index(&self, index: (i32, i32)) -> &Self::Output {
@@ -63,30 +127,31 @@ impl IndexMut<(i32, i32)> for Tilemap {
 }
 
 impl Tilemap {
-    fn load(layout_id: u32, flip_x: bool) -> Self {
-        let layout_sz: usize = 64 * 64;
-        let layout_off = layout_sz * layout_id as usize;
-        let base = &map_data::LAYOUTS[layout_off..layout_off + layout_sz];
-        let out: [[MapTile; 64]; 64] = if flip_x {
-            std::array::from_fn(|y| {
-                let mut row =
-                    std::array::from_fn(|x| unsafe { std::mem::transmute(base[y * 64 + x]) });
+    fn load(data: &DataPack, layout_id: u32, flip_x: bool) -> Self {
+        let mut tiles = map_data::unpack_tiles(data.layout(layout_id));
+        let mut out = [[MapTile::Wall; 64]; 64];
+        for row in out.iter_mut() {
+            for tile in row.iter_mut() {
+                *tile = unsafe { std::mem::transmute::<u8, MapTile>(tiles.next().unwrap()) };
+            }
+            if flip_x {
                 row.reverse();
-                row
-            })
-        } else {
-            std::array::from_fn(|y| {
-                std::array::from_fn(|x| unsafe { std::mem::transmute(base[y * 64 + x]) })
-            })
-        };
+            }
+        }
         Tilemap(out)
     }
 }
 
 struct DungeonFloorState {
+    data: Arc<DataPack>,
     rng: rng::DotnetRng,
     map: Tilemap,
     set_pieces: Vec<(i32, i32, i32)>,
+    // (row, col) of the variant picked for each set piece
+    piece_choices: Vec<(i32, i32)>,
+    // set piece that each goodie returned by load_set_pieces came from, and the tile it's on
+    loot_sources: Vec<(SetPiece, (i32, i32))>,
+    flip_x: bool,
     settings: GameSettings,
     level: i32,
     layout_id: u32,
@@ -94,155 +159,120 @@ struct DungeonFloorState {
     max_luck: f64,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
-enum CommonChest {
-    CinderShards,
-    GoldenCoconut,
-    TaroTuber,
-    PineappleSeeds,
-    ProtectionRing,
-    SoulSapperRing,
-    DwarfSword,
-    DwarfHammer,
-    DwarfDagger,
+struct ChestItem {
+    id: &'static str,
+    count: u32,
+    icon: &'static str,
+}
+
+#[derive(Clone, Copy)]
+enum LootCondition {
+    CrackedGoldenCoconut,
+}
+
+impl LootCondition {
+    fn met(self, settings: GameSettings) -> bool {
+        match self {
+            Self::CrackedGoldenCoconut => settings.cracked_golden_coconut,
+        }
+    }
+}
+
+// one outcome of the chest's first roll. if it has several items, a second roll picks one.
+struct ChestRoll<T: 'static> {
+    condition: Option<LootCondition>,
+    items: &'static [T],
+}
+
+// the CommonChest and RareChest enums and their tables, from game_data/chest_loot.txt
+include!(concat!(env!("OUT_DIR"), "/chest_loot.rs"));
+
+fn roll_chest<T: Copy>(seed: i32, settings: GameSettings, rolls: &[ChestRoll<T>]) -> T {
+    let mut rng = rng::DotnetRng::new(seed);
+    rng.next(); // one roll used for rare/normal check
+    let roll = loop {
+        let roll = &rolls[rng.next_range(rolls.len() as i32) as usize];
+        if roll.condition.is_some_and(|x| !x.met(settings)) {
+            continue;
+        }
+        break roll;
+    };
+    match roll.items {
+        [item] => *item,
+        items => items[rng.next_range(items.len() as i32) as usize],
+    }
+}
+
+// item name with the stack size, if there's more than one
+fn item_name(lang: Language, id: &str, count: u32) -> String {
+    let name = i18n::tr(lang, id);
+    if count > 1 {
+        format!("{} ({})", name, count)
+    } else {
+        name.to_string()
+    }
 }
 
 impl Display for CommonChest {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(match self {
-            Self::CinderShards => "Cinder Shard (3)",
-            Self::GoldenCoconut => "Golden Coconut",
-            Self::TaroTuber => "Taro Tuber (8)",
-            Self::PineappleSeeds => "Pineapple Seeds (5)",
-            Self::ProtectionRing => "Protection Ring",
-            Self::SoulSapperRing => "Soul Sapper Ring",
-            Self::DwarfSword => "Dwarf Sword",
-            Self::DwarfHammer => "Dwarf Hammer",
-            Self::DwarfDagger => "Dwarf Dagger",
-        })
+        f.write_str(&self.name(Language::English))
     }
 }
 
 impl CommonChest {
+    /// The game's qualified item id of what's in the chest.
+    pub fn item_id(&self) -> &'static str {
+        Self::ITEMS[*self as usize].id
+    }
+
+    pub fn count(&self) -> u32 {
+        Self::ITEMS[*self as usize].count
+    }
+
+    pub fn name(&self, lang: Language) -> String {
+        item_name(lang, self.item_id(), self.count())
+    }
+
     fn generate(seed: i32, settings: GameSettings) -> Self {
-        let mut rng = rng::DotnetRng::new(seed);
-        rng.next(); // one roll used for rare/normal check
-        let ind = loop {
-            let ind = rng.next_range(7);
-            if ind == 1 && !settings.cracked_golden_coconut {
-                continue;
-            }
-            break ind;
-        };
-        match ind {
-            0 => Self::CinderShards,
-            1 => Self::GoldenCoconut,
-            2 => Self::TaroTuber,
-            3 => Self::PineappleSeeds,
-            4 => Self::ProtectionRing,
-            5 => Self::SoulSapperRing,
-            6 => {
-                [Self::DwarfSword, Self::DwarfHammer, Self::DwarfDagger][rng.next_range(3) as usize]
-            }
-            _ => unreachable!(),
-        }
+        roll_chest(seed, settings, Self::ROLLS)
     }
+
     fn get_icon(&self) -> &'static str {
-        match self {
-            CommonChest::CinderShards => "cinder_shard",
-            CommonChest::GoldenCoconut => "golden_coconut",
-            CommonChest::TaroTuber => "taro_tuber",
-            CommonChest::PineappleSeeds => "pineapple_seeds",
-            CommonChest::ProtectionRing => "protection_ring",
-            CommonChest::SoulSapperRing => "soul_sapper_ring",
-            CommonChest::DwarfSword => "dwarf_sword",
-            CommonChest::DwarfHammer => "dwarf_hammer",
-            CommonChest::DwarfDagger => "dwarf_dagger",
-        }
-    }
-}
-
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
-enum RareChest {
-    CinderShards,
-    MermaidBoots,
-    DragonscaleBoots,
-    GoldenCoconuts,
-    PhoenixRing,
-    HotJavaRing,
-    DragontoothCutlass,
-    DragontoothClub,
-    DragontoothShiv,
-    DeluxePirateHat,
-    OstrichEgg,
+        Self::ITEMS[*self as usize].icon
+    }
 }
 
 impl Display for RareChest {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(match self {
-            Self::CinderShards => "Cinder Shard (10)",
-            Self::MermaidBoots => "Mermaid Boots",
-            Self::DragonscaleBoots => "Dragonscale Boots",
-            Self::GoldenCoconuts => "Golden Coconut (3)",
-            Self::PhoenixRing => "Phoenix Ring",
-            Self::HotJavaRing => "Hot Java Ring",
-            Self::DragontoothCutlass => "Dragontooth Cutlass",
-            Self::DragontoothClub => "Dragontooth Club",
-            Self::DragontoothShiv => "Dragontooth Shiv",
-            Self::DeluxePirateHat => "Deluxe Pirate Hat",
-            Self::OstrichEgg => "Ostrich Egg",
-        })
+        f.write_str(&self.name(Language::English))
     }
 }
 
 impl RareChest {
+    /// The game's qualified item id of what's in the chest.
+    pub fn item_id(&self) -> &'static str {
+        Self::ITEMS[*self as usize].id
+    }
+
+    pub fn count(&self) -> u32 {
+        Self::ITEMS[*self as usize].count
+    }
+
+    pub fn name(&self, lang: Language) -> String {
+        item_name(lang, self.item_id(), self.count())
+    }
+
     fn generate(seed: i32, settings: GameSettings) -> Self {
-        let mut rng = rng::DotnetRng::new(seed);
-        rng.next(); // one roll used for rare/normal check
-        let ind = loop {
-            let ind = rng.next_range(9);
-            if ind == 3 && !settings.cracked_golden_coconut {
-                continue;
-            }
-            break ind;
-        };
-        match ind {
-            0 => Self::CinderShards,
-            1 => Self::MermaidBoots,
-            2 => Self::DragonscaleBoots,
-            3 => Self::GoldenCoconuts,
-            4 => Self::PhoenixRing,
-            5 => Self::HotJavaRing,
-            6 => [
-                Self::DragontoothCutlass,
-                Self::DragontoothClub,
-                Self::DragontoothShiv,
-            ][rng.next_range(3) as usize],
-            7 => Self::DeluxePirateHat,
-            8 => Self::OstrichEgg,
-            _ => unreachable!(),
-        }
+        roll_chest(seed, settings, Self::ROLLS)
     }
 
     fn get_icon(&self) -> &'static str {
-        match self {
-            RareChest::CinderShards => "cinder_shard",
-            RareChest::MermaidBoots => "mermaid_boots",
-            RareChest::DragonscaleBoots => "dragonscale_boots",
-            RareChest::GoldenCoconuts => "golden_coconut",
-            RareChest::PhoenixRing => "phoenix_ring",
-            RareChest::HotJavaRing => "hot_java_ring",
-            RareChest::DragontoothCutlass => "dragontooth_cutlass",
-            RareChest::DragontoothClub => "dragontooth_club",
-            RareChest::DragontoothShiv => "dragontooth_shiv",
-            RareChest::DeluxePirateHat => "deluxe_pirate_hat",
-            RareChest::OstrichEgg => "ostrich_egg",
-        }
+        Self::ITEMS[*self as usize].icon
     }
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
-enum Goodie {
+pub enum Goodie {
     DragonTooth,
     CommonChest(CommonChest),
     RareChest(RareChest),
@@ -253,10 +283,12 @@ enum Goodie {
     },
 }
 
+const DRAGON_TOOTH: &str = "(O)852";
+
 impl Display for Goodie {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Goodie::DragonTooth => write!(f, "Dragon Tooth"),
+            Goodie::DragonTooth => f.write_str(i18n::tr(Language::English, DRAGON_TOOTH)),
             Goodie::CommonChest(c) => write!(f, "common chest: {}", c),
             Goodie::RareChest(c) => write!(f, "rare chest: {}", c),
             Goodie::ChanceChest {
@@ -279,15 +311,36 @@ fn format_icon(name: &str) -> String {
 }
 
 impl Goodie {
-    fn to_html(&self) -> String {
+    // like Display, but with the chance chest threshold in displayed luck units
+    fn describe(&self) -> String {
+        match self {
+            Goodie::ChanceChest {
+                minluck,
+                common,
+                rare,
+            } => format!(
+                "rare chest if luck > {:.4}: {}, else common chest: {}",
+                display_luck(*minluck),
+                rare,
+                common
+            ),
+            _ => self.to_string(),
+        }
+    }
+
+    fn to_html(&self, lang: Language) -> String {
         match self {
-            Goodie::DragonTooth => format!("{} Dragon Tooth", format_icon("dragon_tooth")),
+            Goodie::DragonTooth => format!(
+                "{} {}",
+                format_icon("dragon_tooth"),
+                i18n::tr(lang, DRAGON_TOOTH)
+            ),
             Goodie::CommonChest(c) => {
                 format!(
                     "{} {} {}",
                     format_icon("common_chest"),
                     format_icon(c.get_icon()),
-                    c
+                    c.name(lang)
                 )
             }
             Goodie::RareChest(c) => {
@@ -295,39 +348,55 @@ impl Goodie {
                     "{} {} {}",
                     format_icon("rare_chest"),
                     format_icon(c.get_icon()),
-                    c
+                    c.name(lang)
                 )
             }
-            // shouldn't ever be turned into html
-            Goodie::ChanceChest { .. } => self.to_string(),
+            Goodie::ChanceChest {
+                minluck,
+                common,
+                rare,
+            } => i18n::tr_args(
+                lang,
+                "goodies.chance_chest",
+                &[
+                    ("luck", &format!("{:.4}", display_luck(*minluck))),
+                    ("rare", &rare.name(lang)),
+                    ("common", &common.name(lang)),
+                ],
+            ),
         }
     }
 }
 
+// the seed everything on a floor is generated from, apart from the layout
+fn floor_gen_seed(settings: GameSettings, level: i32) -> i32 {
+    let lvl_mod = if settings.version >= GameVersion::V1_6_4 {
+        level + 1
+    } else {
+        level
+    };
+    rng::stardew_seed_mix(
+        settings.legacy_rng,
+        &[
+            (settings.days_played * lvl_mod as u32) as f64,
+            (level * 5152) as f64,
+            (settings.seed / 2) as f64,
+        ],
+    )
+}
+
 impl DungeonFloorState {
     fn new(
+        data: Arc<DataPack>,
         settings: GameSettings,
         level: i32,
         layout_id: u32,
         min_luck: f64,
         max_luck: f64,
     ) -> Self {
-        let lvl_mod = if settings.post_1_6_4 {
-            level + 1
-        } else {
-            level
-        };
-        let gen_seed = rng::stardew_seed_mix(
-            settings.legacy_rng,
-            &[
-                (settings.days_played * lvl_mod as u32) as f64,
-                (level * 5152) as f64,
-                (settings.seed / 2) as f64,
-            ],
-        );
         let mut gen_random = rng::DotnetRng::new(rng::stardew_seed_mix(
             settings.legacy_rng,
-            &[gen_seed as f64],
+            &[floor_gen_seed(settings, level) as f64],
         ));
         gen_random.next();
         let mut flip_x = gen_random.next_range(2) == 1;
@@ -335,9 +404,13 @@ impl DungeonFloorState {
             flip_x = false;
         }
         Self {
+            map: Tilemap::load(&data, layout_id, flip_x),
+            data,
             rng: gen_random,
-            map: Tilemap::load(layout_id, flip_x),
             set_pieces: vec![],
+            piece_choices: vec![],
+            loot_sources: vec![],
+            flip_x,
             level,
             layout_id,
             settings,
@@ -356,87 +429,51 @@ impl DungeonFloorState {
     }
 
     fn load_map_tiles(&mut self) {
-        // floor tile type generation (we don't care about the result, but need RNG to sync)
-        for _x in 0..64 {
-            for _y in 0..64 {
-                if self.rng.next_f64() < 0.3_f32 as f64 {
-                    self.rng.next();
-                    self.rng.next();
-                }
-            }
-        }
+        // floor tile type generation (we don't care about the result, but need RNG to sync).
+        // for every tile: if next_f64() < 0.3, two more rolls
+        self.rng.skip_chance_rolls(64 * 64, 0.3_f32 as f64);
     }
 
     fn load_set_pieces(&mut self) -> Vec<Goodie> {
-        let mut buggy = false;
-        for x in 0_i32..64 {
-            for y in 0_i32..64 {
-                if self.map[(x, y)] == MapTile::SetPiece {
-                    let mut j = 0_i32;
-                    while j < 64
-                        && self.map[(x + j, y)] == MapTile::SetPiece
-                        && self.map[(x, y + j)] == MapTile::SetPiece
-                    {
-                        j += 1;
-                    }
-                    for y in y..y + j {
-                        for x in x..x + j {
-                            self.map[(x, y)] = MapTile::Floor;
-                        }
-                    }
-                    if !matches!(j, 32 | 16 | 8 | 4 | 3) {
-                        println!(
-                            "warning: buggy map? layout={} at x={} y={}, size={}",
-                            self.layout_id, x, y, j
-                        );
-                        buggy = true;
-                    }
-                    let realj = match j {
-                        32.. => 32,
-                        16.. => 16,
-                        8.. => 8,
-                        4.. => 4,
-                        _ => 3,
-                    };
-                    self.set_pieces.push((x, y, realj));
+        let data = self.data.clone();
+        let info = data.layout_info(self.layout_id, self.flip_x);
+        // the game clears every square it found, draw the ones it actually uses back in.
+        // squares with odd sizes are reported by `validate_layouts`.
+        for &(x, y, found, _) in info.set_pieces.iter() {
+            for y in y..y + found {
+                for x in x..x + found {
+                    self.map[(x, y)] = MapTile::Floor;
                 }
             }
         }
-        // re-add the real set piece locations for rendering
-        for &(x, y, j) in &self.set_pieces {
-            for y in y..y + j {
-                for x in x..x + j {
+        for &(x, y, _, size) in info.set_pieces.iter() {
+            for y in y..(y + size).min(64) {
+                for x in x..(x + size).min(64) {
                     self.map[(x, y)] = MapTile::SetPiece;
                 }
             }
+            self.set_pieces.push((x, y, size));
         }
         let mut goodies = vec![];
 
         for (x, y, set_size) in self.set_pieces.iter().cloned() {
-            let (num_rows, num_cols) = map_data::get_piece_sizes(set_size);
+            let (num_rows, num_cols) = data.piece_sizes(set_size);
             let selected_col = self.rng.next_range(num_cols);
             let selected_row = self.rng.next_range(num_rows);
-            if buggy {
-                println!(
-                    "layout {}: x={} y={} sz={} selected row {}, col {}",
-                    self.layout_id, x, y, set_size, selected_row, selected_col
-                );
-            }
-            let events = map_data::get_piece_events()
-                .get(&(set_size, selected_row, selected_col))
-                .copied();
-            let events = events.unwrap_or(&[]);
-            for &ev in events {
+            self.piece_choices.push((selected_row, selected_col));
+            for &ev in data.piece_events(set_size, selected_row, selected_col) {
                 match ev {
                     map_data::SetPieceFeature::Rng => {
                         self.rng.next();
                     }
-                    map_data::SetPieceFeature::Tooth => {
+                    map_data::SetPieceFeature::Tooth { x: dx, y: dy } => {
                         if self.rng.next_f64() < 0.5 {
                             goodies.push(Goodie::DragonTooth);
+                            self.loot_sources.push(((x, y, set_size), (x + dx, y + dy)));
                         }
                     }
-                    map_data::SetPieceFeature::Chest => {
+                    map_data::SetPieceFeature::Chest { x: dx, y: dy } => {
+                        self.loot_sources.push(((x, y, set_size), (x + dx, y + dy)));
                         // TODO: does not go through seedmix in 1.5
                         // (though, legacy seedmix with 1 arg is mostly identity anyways...)
                         let chest_seed = rng::stardew_seed_mix(
@@ -479,7 +516,13 @@ impl DungeonFloorState {
     }
 }
 
-fn compute_volcano_layouts(settings: GameSettings) -> Vec<(f64, f64, [u32; 10])> {
+#[allow(clippy::needless_return)]
+pub fn compute_volcano_layouts(settings: GameSettings) -> ProbabilityRange<[u32; 10]> {
+    #[allow(
+        clippy::needless_return,
+        clippy::neg_cmp_op_on_partial_ord,
+        clippy::collapsible_if
+    )]
     fn compute_inner(
         settings: GameSettings,
         prev: &[u32],
@@ -487,7 +530,7 @@ fn compute_volcano_layouts(settings: GameSettings) -> Vec<(f64, f64, [u32; 10])>
         maxluck: f64,
     ) -> Vec<(f64, f64, [u32; 10])> {
         let level = prev.len();
-        let lvl_mod = if settings.post_1_6_4 {
+        let lvl_mod = if settings.version >= GameVersion::V1_6_4 {
             level + 1
         } else {
             level
@@ -543,7 +586,7 @@ fn compute_volcano_layouts(settings: GameSettings) -> Vec<(f64, f64, [u32; 10])>
                 }
             }
         }
-        if level > 0 && settings.post_1_6_4 && settings.has_caldera {
+        if level > 0 && settings.version >= GameVersion::V1_6_4 && settings.has_caldera {
             if layout_random.next_f64() < 0.75 {
                 valid_layouts.extend(38..58);
             }
@@ -557,6 +600,12 @@ fn compute_volcano_layouts(settings: GameSettings) -> Vec<(f64, f64, [u32; 10])>
         lvlbuf.push(the_layout);
         return compute_inner(settings, &lvlbuf, minluck, maxluck);
     }
+    let (minluck, maxluck) = luck_bounds(settings);
+    return compute_inner(settings, &[], minluck, maxluck);
+}
+
+// full luckMult range that's possible with these settings
+fn luck_bounds(settings: GameSettings) -> (f64, f64) {
     // these values are *technically* not exact due to rounding (special charm especially)
     // but we only show them with 4 significant digits anyways
     let mut minluck = -0.1;
@@ -565,12 +614,10 @@ fn compute_volcano_layouts(settings: GameSettings) -> Vec<(f64, f64, [u32; 10])>
         minluck += 0.025_f32 as f64;
         base_maxluck += 0.025_f32 as f64;
     }
-    return compute_inner(
-        settings,
-        &[],
+    (
         1. + minluck / 2.,
         1. + base_maxluck / 2. + 0.035 * (settings.max_luck_lvl as f64),
-    );
+    )
 }
 
 #[allow(unused_macros)]
@@ -589,14 +636,99 @@ macro_rules! console_log {
 }
 
 // (minluck, maxluck, item)
-type ProbabilityRange<T> = Vec<(f64, f64, T)>;
+pub type ProbabilityRange<T> = Vec<(f64, f64, T)>;
 
-fn do_dungeon(
+pub struct DungeonPrediction {
+    pub settings: GameSettings,
+    pub layouts: [ProbabilityRange<u32>; 10],
+    pub loots: [ProbabilityRange<Vec<Goodie>>; 10],
+}
+
+// everything that decides what a floor contains, except luck
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct FloorKey {
+    gen_seed: i32,
+    legacy_rng: bool,
+    cracked_golden_coconut: bool,
+    level: i32,
+    layout: u32,
+}
+
+/// Loot of the floors generated so far. Luck only decides whether each chest is rare or common,
+/// so luck ranges that end up on the same layout, or the same day with a different luck buff,
+/// share one floor here, with the chests kept as `ChanceChest`.
+#[derive(Default)]
+pub struct FloorCache {
+    // the map data the floors were generated with
+    data: Option<Arc<DataPack>>,
+    floors: HashMap<FloorKey, Vec<Goodie>>,
+}
+
+impl FloorCache {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The loot of a floor for luck in `min_luck..=max_luck`, same as `DungeonFloorState` would
+    /// give.
+    fn loot(
+        &mut self,
+        settings: GameSettings,
+        level: i32,
+        layout: u32,
+        min_luck: f64,
+        max_luck: f64,
+    ) -> Result<Vec<Goodie>, DataPackError> {
+        let data = data_pack_for(&settings)?;
+        if !self.data.as_ref().is_some_and(|x| Arc::ptr_eq(x, &data)) {
+            self.floors.clear();
+            self.data = Some(data.clone());
+        }
+        let key = FloorKey {
+            gen_seed: floor_gen_seed(settings, level),
+            legacy_rng: settings.legacy_rng,
+            cracked_golden_coconut: settings.cracked_golden_coconut,
+            level,
+            layout,
+        };
+        let loot = self.floors.entry(key).or_insert_with(|| {
+            // no luck is low or high enough to decide any chest
+            DungeonFloorState::new(
+                data,
+                settings,
+                level,
+                layout,
+                f64::NEG_INFINITY,
+                f64::INFINITY,
+            )
+            .load_map()
+        });
+        Ok(loot
+            .iter()
+            .map(|goodie| match *goodie {
+                Goodie::ChanceChest { minluck, rare, .. } if minluck < min_luck => {
+                    Goodie::RareChest(rare)
+                }
+                Goodie::ChanceChest {
+                    minluck, common, ..
+                } if minluck >= max_luck => Goodie::CommonChest(common),
+                ref x => x.clone(),
+            })
+            .collect())
+    }
+}
+
+/// The layout and loot of every floor, for each luck range. Fails when there's no map data
+/// for the game version.
+pub fn do_dungeon(settings: GameSettings) -> Result<DungeonPrediction, DataPackError> {
+    do_dungeon_cached(settings, &mut FloorCache::new())
+}
+
+/// Same as `do_dungeon`, but reusing floors from `cache`, and adding new ones to it.
+pub fn do_dungeon_cached(
     settings: GameSettings,
-) -> (
-    [ProbabilityRange<u32>; 10],
-    [ProbabilityRange<Vec<Goodie>>; 10],
-) {
+    cache: &mut FloorCache,
+) -> Result<DungeonPrediction, DataPackError> {
     let mut layouts_poss = [(); 10].map(|_| ProbabilityRange::<u32>::new());
     let mut loots_poss = [(); 10].map(|_| ProbabilityRange::<Vec<Goodie>>::new());
     for (minluck, maxluck, lvls) in compute_volcano_layouts(settings) {
@@ -609,8 +741,7 @@ fn do_dungeon(
             } else {
                 layouts_poss[i].push((minluck, maxluck, x));
             }
-            let mut h = DungeonFloorState::new(settings, i as i32, x, minluck, maxluck);
-            let loot = h.load_map();
+            let loot = cache.loot(settings, i as i32, x, minluck, maxluck)?;
             fn handle_loot(
                 minluck: f64,
                 maxluck: f64,
@@ -667,7 +798,11 @@ fn do_dungeon(
             handle_loot(minluck, maxluck, loot, &mut loots_poss[i]);
         }
     }
-    (layouts_poss, loots_poss)
+    Ok(DungeonPrediction {
+        settings,
+        layouts: layouts_poss,
+        loots: loots_poss,
+    })
 }
 
 fn display_luck(luck: f64) -> f64 {
@@ -677,30 +812,79 @@ fn display_luck(luck: f64) -> f64 {
     (luck - 1.) * 2.
 }
 
+// e.g. "summer 3, Y1"
+fn format_date(days_played: u32) -> String {
+    let total_seasons = (days_played - 1) / 28;
+    let year = total_seasons / 4 + 1;
+    let season = total_seasons % 4;
+    let day = (days_played - 1) % 28 + 1;
+    format!(
+        "{} {day}, Y{year}",
+        ["spring", "summer", "fall", "winter"][season as usize],
+    )
+}
+
+#[allow(clippy::manual_range_contains)]
 fn is_mushroom_floor(layout: u32) -> bool {
     layout >= 32 && layout <= 34
 }
+#[allow(clippy::manual_range_contains)]
 fn is_monster_floor(layout: u32) -> bool {
     layout >= 35 && layout <= 37
 }
 
-#[wasm_bindgen]
-pub fn main_update(settings: GameSettings) -> String {
-    console_error_panic_hook::set_once();
-    let mut out = String::new();
-    let (layouts, loots) = do_dungeon(settings);
+// do_dungeon, narrowed down by the observations if there are any. the notes say what luck
+// range the observations leave, or that they were ignored.
+fn observed_prediction(
+    settings: GameSettings,
+    observations: &Observations,
+    lang: Language,
+) -> Result<(DungeonPrediction, Vec<String>), DataPackError> {
+    let prediction = do_dungeon(settings)?;
+    let mut notes = vec![];
+    if observations.is_empty() {
+        return Ok((prediction, notes));
+    }
+    let luck = narrow_luck(&prediction, observations.as_slice());
+    if luck.is_empty() {
+        notes.push(i18n::tr(lang, "observed.contradict").to_string());
+        return Ok((prediction, notes));
+    }
+    let ranges: Vec<_> = luck
+        .iter()
+        .map(|&(a, b)| {
+            let min = format!("{:.4}", display_luck(a));
+            let max = format!("{:.4}", display_luck(b));
+            i18n::tr_args(lang, "observed.range", &[("min", &min), ("max", &max)])
+        })
+        .collect();
+    notes.push(i18n::tr_args(
+        lang,
+        "observed.luck",
+        &[("ranges", &ranges.join(", "))],
+    ));
+    Ok((prediction.restrict(&luck), notes))
+}
 
-    let total_seasons = (settings.days_played - 1) / 28;
-    let year = total_seasons / 4 + 1;
-    let season = total_seasons % 4;
-    let day = (settings.days_played - 1) % 28 + 1;
-    writeln!(
-        out,
-        "day: {} {day}, Y{year}",
-        ["spring", "summer", "fall", "winter"][season as usize],
-    )
-    .unwrap();
+// dragon teeth are merged into one entry with a count, everything else is listed as is
+fn group_loot(loot: &[Goodie]) -> Vec<(&Goodie, usize)> {
+    let num_dragon_teeth = loot
+        .iter()
+        .filter(|x| matches!(x, Goodie::DragonTooth))
+        .count();
+    let mut out = vec![];
+    if num_dragon_teeth > 0 {
+        out.push((&Goodie::DragonTooth, num_dragon_teeth));
+    }
+    for l in loot {
+        if *l != Goodie::DragonTooth {
+            out.push((l, 1));
+        }
+    }
+    out
+}
 
+fn layouts_html(layouts: &[ProbabilityRange<u32>; 10]) -> String {
     fn format_layout(level: usize, layout: u32) -> String {
         let displayname = if is_mushroom_floor(layout) {
             format!("{} {}", format_icon("magma_cap"), layout)
@@ -743,7 +927,10 @@ pub fn main_update(settings: GameSettings) -> String {
     layouts_full += "</tr><tr>";
     layouts_full += &layouts_disp;
     layouts_full += "</tr></table>";
+    layouts_full
+}
 
+fn goodies_html(loots: &[ProbabilityRange<Vec<Goodie>>; 10], lang: Language) -> String {
     let mut goodies_out = String::new();
 
     macro_rules! out {
@@ -752,55 +939,223 @@ pub fn main_update(settings: GameSettings) -> String {
         };
     }
 
-    for (i, floor_loot) in loots.into_iter().enumerate() {
+    for (i, floor_loot) in loots.iter().enumerate() {
         if floor_loot.iter().all(|y| y.2.is_empty()) {
             continue;
         }
 
-        out!("<div><b>floor {}:</b><ul>", i);
-        for (minl, maxl, loot) in &floor_loot {
+        out!(
+            "<div><b>{}</b><ul>",
+            i18n::tr_args(lang, "goodies.floor", &[("floor", &i.to_string())])
+        );
+        for (minl, maxl, loot) in floor_loot {
             if floor_loot.len() > 1 {
+                let min = format!("{:.4}", display_luck(*minl));
+                let max = format!("{:.4}", display_luck(*maxl));
                 out!(
-                    "<li>luck {:.4} to {:.4}:</li>",
-                    display_luck(*minl),
-                    display_luck(*maxl)
+                    "<li>{}</li>",
+                    i18n::tr_args(lang, "goodies.luck", &[("min", &min), ("max", &max)])
                 );
             }
             out!("<ul>");
             if loot.is_empty() {
-                out!("<li>[nothing]</li>");
-            }
-            let num_dragon_teeth = loot
-                .iter()
-                .filter(|x| matches!(x, Goodie::DragonTooth))
-                .count();
-            if num_dragon_teeth > 1 {
-                out!(
-                    "<li>{} ({})</li>",
-                    Goodie::DragonTooth.to_html(),
-                    num_dragon_teeth
-                );
-            } else if num_dragon_teeth > 0 {
-                out!("<li>{}</li>", Goodie::DragonTooth.to_html());
+                out!("<li>{}</li>", i18n::tr(lang, "goodies.nothing"));
             }
-            for l in loot {
-                if *l != Goodie::DragonTooth {
-                    out!("<li>{}</li>", l.to_html());
+            for (l, count) in group_loot(loot) {
+                if count > 1 {
+                    out!("<li>{} ({})</li>", l.to_html(lang), count);
+                } else {
+                    out!("<li>{}</li>", l.to_html(lang));
                 }
             }
             out!("</ul>");
         }
         out!("</ul></div>");
     }
+    goodies_out
+}
+
+// the day summary, loot and layout buttons that `main_update` puts on the page
+fn update_parts(
+    settings: GameSettings,
+    observations: &Observations,
+    lang: Language,
+) -> Result<(String, String, String), DataPackError> {
+    let mut out = String::new();
+
+    writeln!(out, "day: {}", format_date(settings.days_played)).unwrap();
+
+    let (prediction, notes) = observed_prediction(settings, observations, lang)?;
+    for note in notes {
+        writeln!(out, "{}", note).unwrap();
+    }
+
+    Ok((
+        out,
+        goodies_html(&prediction.loots, lang),
+        layouts_html(&prediction.layouts),
+    ))
+}
+
+#[wasm_bindgen]
+#[allow(clippy::needless_return)]
+pub fn main_update(
+    settings: GameSettings,
+    observations: &Observations,
+    lang: Language,
+) -> Result<String, JsError> {
+    console_error_panic_hook::set_once();
+    let (out, goodies, layouts) = update_parts(settings, observations, lang)?;
+
     let doc = web_sys::window().unwrap().document().unwrap();
     doc.get_element_by_id("goodies")
         .unwrap()
-        .set_inner_html(&goodies_out);
+        .set_inner_html(&goodies);
     doc.get_element_by_id("map-sel")
         .unwrap()
-        .set_inner_html(&layouts_full);
+        .set_inner_html(&layouts);
 
-    return out;
+    return Ok(out);
+}
+
+/// Same as `main_update`, but without touching the page, so it works in a web worker. Returns
+/// [text, goodies html, layout buttons html].
+#[wasm_bindgen]
+pub fn update_data(
+    settings: GameSettings,
+    observations: &Observations,
+    lang: Language,
+) -> Result<js_sys::Array, JsError> {
+    console_error_panic_hook::set_once();
+    let (out, goodies, layouts) = update_parts(settings, observations, lang)?;
+    Ok([out, goodies, layouts]
+        .into_iter()
+        .map(JsValue::from)
+        .collect())
+}
+
+/// The tiles of a generated floor, with set pieces marked at their real size.
+pub fn floor_tiles(
+    settings: GameSettings,
+    lvl: i32,
+    layout: u32,
+) -> Result<Tilemap, DataPackError> {
+    // TODO: currently the map rendering does not depend on luck, so we can just use a dummy value
+    // for it. might need to track it properly later tho
+    let data = data_pack_for(&settings)?;
+    let mut floor = DungeonFloorState::new(data, settings, lvl, layout, 0., 0.);
+    floor.load_map();
+    Ok(floor.get_tiles())
+}
+
+// (x, y, size)
+pub type SetPiece = (i32, i32, i32);
+
+pub struct GeneratedFloor {
+    pub tiles: Tilemap,
+    pub flip_x: bool,
+    /// every set piece (x, y, size), with the (row, col) of the variant that was stamped in
+    pub set_pieces: Vec<(SetPiece, (i32, i32))>,
+    /// each goodie together with the set piece (x, y, size) it's in. chests whose contents
+    /// depend on luck are left as `ChanceChest`.
+    pub loot: Vec<(SetPiece, Goodie)>,
+    /// the (x, y) tile of each goodie in `loot`
+    pub loot_pos: Vec<(i32, i32)>,
+}
+
+impl GeneratedFloor {
+    /// Each goodie with the tile it's on. The game also looks one row below a set piece, and
+    /// squares by the edge get cut off, so the ones that end up off the map are left out.
+    pub fn loot_tiles(&self) -> Vec<(i32, i32, &Goodie)> {
+        self.loot_pos
+            .iter()
+            .zip(&self.loot)
+            .filter(|((x, y), _)| (0..64).contains(x) && (0..64).contains(y))
+            .map(|(&(x, y), (_, goodie))| (x, y, goodie))
+            .collect()
+    }
+}
+
+pub fn generate_floor(
+    settings: GameSettings,
+    lvl: i32,
+    layout: u32,
+) -> Result<GeneratedFloor, DataPackError> {
+    let (minluck, maxluck) = luck_bounds(settings);
+    generate_floor_in_range(settings, lvl, layout, minluck, maxluck)
+}
+
+/// Like `generate_floor`, but only chests that can go either way within `minluck..=maxluck`
+/// are left as `ChanceChest`.
+pub fn generate_floor_in_range(
+    settings: GameSettings,
+    lvl: i32,
+    layout: u32,
+    minluck: f64,
+    maxluck: f64,
+) -> Result<GeneratedFloor, DataPackError> {
+    let data = data_pack_for(&settings)?;
+    let mut floor = DungeonFloorState::new(data, settings, lvl, layout, minluck, maxluck);
+    let goodies = floor.load_map();
+    Ok(GeneratedFloor {
+        tiles: floor.get_tiles(),
+        flip_x: floor.flip_x,
+        set_pieces: floor
+            .set_pieces
+            .iter()
+            .copied()
+            .zip(floor.piece_choices.iter().copied())
+            .collect(),
+        loot: floor
+            .loot_sources
+            .iter()
+            .map(|x| x.0)
+            .zip(goodies)
+            .collect(),
+        loot_pos: floor.loot_sources.iter().map(|x| x.1).collect(),
+    })
+}
+
+// the notes shown under the map preview
+fn map_notes(
+    settings: GameSettings,
+    lvl: i32,
+    layout: u32,
+    floor: &GeneratedFloor,
+    lang: Language,
+) -> Result<String, DataPackError> {
+    let has_buttons = (0..64)
+        .flat_map(|y| (0..64).map(move |x| (x, y)))
+        .any(|pos| floor.tiles[pos] == MapTile::SwitchLocation);
+    let mut lines = vec![];
+    if is_mushroom_floor(layout) {
+        lines.push("floor.mushroom");
+    }
+    if is_monster_floor(layout) {
+        lines.push("floor.monster");
+    }
+    if lvl != 9 {
+        if has_buttons && !is_monster_floor(layout) {
+            lines.push("floor.gate_chance");
+        }
+        if has_buttons {
+            if is_monster_floor(layout) {
+                lines.push("floor.buttons_three");
+            } else {
+                lines.push("floor.buttons_random");
+            }
+        }
+    }
+    let mut out: String = lines
+        .into_iter()
+        .map(|key| format!("{}<br>", i18n::tr(lang, key)))
+        .collect();
+    let data = data_pack_for(&settings)?;
+    let info = data.layout_info(layout, floor.flip_x);
+    for anomaly in game_data::validate_layout(layout, floor.flip_x, info) {
+        writeln!(out, "{}<br>", anomaly.describe(lang)).unwrap();
+    }
+    Ok(out)
 }
 
 #[wasm_bindgen]
@@ -811,19 +1166,12 @@ pub fn render_map(
     canvas: CanvasRenderingContext2d,
     tile_img: HtmlImageElement,
     tile_sz: usize,
-) -> String {
-    // TODO: currently the map rendering does not depend on luck, so we can just use a dummy value
-    // for it. might need to track it properly later tho
-    let mut floor = DungeonFloorState::new(settings, lvl, layout, 0., 0.);
-    floor.load_map();
-    let mut has_buttons = false;
-    let tiles = floor.get_tiles();
+    lang: Language,
+) -> Result<String, JsError> {
+    let floor = generate_floor(settings, lvl, layout)?;
     for y in 0..64 {
         for x in 0..64 {
-            let tile = tiles[(x, y)];
-            if let MapTile::SwitchLocation = tile {
-                has_buttons = true;
-            }
+            let tile = floor.tiles[(x, y)];
             let tile_off = tile_sz * tile as u8 as usize;
             let tile_sz = tile_sz as f64;
             canvas
@@ -841,26 +1189,332 @@ pub fn render_map(
                 .unwrap();
         }
     }
-    let mut out = String::new();
-    if is_mushroom_floor(layout) {
-        out += "Mushroom floor: there's lots of Magma Caps and False Magma Caps here.<br>";
+    Ok(map_notes(settings, lvl, layout, &floor, lang)?)
+}
+
+/// Same as `render_map`, but for a web worker: returns [tiles, notes], the tiles being one byte
+/// per tile, rows top to bottom, each the tile's index in the map tile sheet.
+#[wasm_bindgen]
+pub fn map_data(
+    settings: GameSettings,
+    lvl: i32,
+    layout: u32,
+    lang: Language,
+) -> Result<js_sys::Array, JsError> {
+    let floor = generate_floor(settings, lvl, layout)?;
+    let tiles: Vec<u8> = (0..64)
+        .flat_map(|y| (0..64).map(move |x| (x, y)))
+        .map(|pos| floor.tiles[pos] as u8)
+        .collect();
+    let notes = map_notes(settings, lvl, layout, &floor, lang)?;
+    Ok([
+        JsValue::from(js_sys::Uint8Array::from(&tiles[..])),
+        JsValue::from(notes),
+    ]
+    .into_iter()
+    .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packed_layouts_match_png() {
+        let raw = game_data::decode_layouts(include_bytes!("../game_data/Layouts.png")).unwrap();
+        let data = DataPack::builtin();
+        assert_eq!(data.layout_count(), raw.len() / (64 * 64));
+        for (layout_id, tiles) in raw.chunks(64 * 64).enumerate() {
+            for flip_x in [false, true] {
+                let map = Tilemap::load(&data, layout_id as u32, flip_x);
+                for y in 0..64 {
+                    for x in 0..64 {
+                        let src_x = if flip_x { 63 - x } else { x };
+                        assert_eq!(
+                            map[(x, y)] as u8,
+                            tiles[(y * 64 + src_x) as usize],
+                            "layout {} flip {} at x={} y={}",
+                            layout_id,
+                            flip_x,
+                            x,
+                            y
+                        );
+                    }
+                }
+            }
+        }
     }
-    if is_monster_floor(layout) {
-        out += "Monster floor: there's lots of enemies and a guaranteed dwarf gate around the exit here.<br>";
+
+    #[test]
+    fn layout_info_matches_old_scan() {
+        // how `load_set_pieces` used to find the set pieces, on the map itself
+        fn old_scan(mut map: Tilemap) -> Vec<(i32, i32, i32, i32)> {
+            let mut out = vec![];
+            for x in 0_i32..64 {
+                for y in 0_i32..64 {
+                    if map[(x, y)] == MapTile::SetPiece {
+                        let mut j = 0_i32;
+                        while j < 64
+                            && map[(x + j, y)] == MapTile::SetPiece
+                            && map[(x, y + j)] == MapTile::SetPiece
+                        {
+                            j += 1;
+                        }
+                        for y in y..y + j {
+                            for x in x..x + j {
+                                map[(x, y)] = MapTile::Floor;
+                            }
+                        }
+                        let realj = match j {
+                            32.. => 32,
+                            16.. => 16,
+                            8.. => 8,
+                            4.. => 4,
+                            _ => 3,
+                        };
+                        out.push((x, y, j, realj));
+                    }
+                }
+            }
+            out
+        }
+        let data = DataPack::builtin();
+        for layout_id in 0..data.layout_count() as u32 {
+            for flip_x in [false, true] {
+                assert_eq!(
+                    data.layout_info(layout_id, flip_x).set_pieces[..],
+                    old_scan(Tilemap::load(&data, layout_id, flip_x)),
+                    "layout {} flip {}",
+                    layout_id,
+                    flip_x
+                );
+            }
+        }
     }
-    if lvl != 9 {
-        if has_buttons && !is_monster_floor(layout) {
-            out += "This floor has a 20% chance of generating a dwarf gate around the exit.<br>";
+
+    #[test]
+    fn chest_loot_like_before() {
+        // the match arms the chests used before the tables came from chest_loot.txt
+        fn old_common(rng: &mut rng::DotnetRng, coconut: bool) -> CommonChest {
+            use CommonChest::*;
+            let ind = loop {
+                let ind = rng.next_range(7);
+                if ind == 1 && !coconut {
+                    continue;
+                }
+                break ind;
+            };
+            match ind {
+                0 => CinderShards,
+                1 => GoldenCoconut,
+                2 => TaroTuber,
+                3 => PineappleSeeds,
+                4 => ProtectionRing,
+                5 => SoulSapperRing,
+                6 => [DwarfSword, DwarfHammer, DwarfDagger][rng.next_range(3) as usize],
+                _ => unreachable!(),
+            }
         }
-        if has_buttons {
-            out += "When a dwarf gate generates, it'll randomly choose ";
-            if is_monster_floor(layout) {
-                out += "3";
-            } else {
-                out += "1 to 3";
+        fn old_rare(rng: &mut rng::DotnetRng, coconut: bool) -> RareChest {
+            use RareChest::*;
+            let ind = loop {
+                let ind = rng.next_range(9);
+                if ind == 3 && !coconut {
+                    continue;
+                }
+                break ind;
+            };
+            match ind {
+                0 => CinderShards,
+                1 => MermaidBoots,
+                2 => DragonscaleBoots,
+                3 => GoldenCoconuts,
+                4 => PhoenixRing,
+                5 => HotJavaRing,
+                6 => [DragontoothCutlass, DragontoothClub, DragontoothShiv]
+                    [rng.next_range(3) as usize],
+                7 => DeluxePirateHat,
+                8 => OstrichEgg,
+                _ => unreachable!(),
             }
-            out += " of the possible button positions and generate buttons there.<br>";
+        }
+        let mut settings = GameSettings::new();
+        for coconut in [false, true] {
+            settings.cracked_golden_coconut = coconut;
+            for seed in (0..2000).map(|x| x * 1_000_003 - 7) {
+                let rng = || {
+                    let mut rng = rng::DotnetRng::new(seed);
+                    rng.next(); // the rare/common roll
+                    rng
+                };
+                let common = CommonChest::generate(seed, settings);
+                assert_eq!(common, old_common(&mut rng(), coconut), "seed {}", seed);
+                let rare = RareChest::generate(seed, settings);
+                assert_eq!(rare, old_rare(&mut rng(), coconut), "seed {}", seed);
+            }
+        }
+        assert_eq!(
+            CommonChest::TaroTuber.name(Language::English),
+            "Taro Tuber (8)"
+        );
+        assert_eq!(CommonChest::DwarfDagger.item_id(), "(W)56");
+        assert_eq!(
+            RareChest::CinderShards.name(Language::English),
+            "Cinder Shard (10)"
+        );
+        assert_eq!(RareChest::DeluxePirateHat.item_id(), "(H)76");
+    }
+
+    #[test]
+    fn predicts_1_5_like_before() {
+        // a 1.5 save as the page used to predict it: legacy random and no 1.6.4 changes
+        let mut settings = GameSettings::new();
+        settings.seed = 123456789;
+        settings.legacy_rng = true;
+        settings.version = GameVersion::V1_5;
+        settings.days_played = 47;
+        settings.max_luck_lvl = 2;
+        let prediction = do_dungeon(settings).unwrap();
+        let layouts: Vec<Vec<u32>> = prediction
+            .layouts
+            .iter()
+            .map(|x| x.iter().map(|x| x.2).collect())
+            .collect();
+        assert_eq!(
+            layouts,
+            [
+                vec![0],
+                vec![13],
+                vec![24, 29],
+                vec![9],
+                vec![23],
+                vec![31],
+                vec![22],
+                vec![7],
+                vec![26],
+                vec![30]
+            ]
+        );
+        assert_eq!(prediction.layouts[2][0].1, 1.0690015913308606);
+        let loots: Vec<Vec<Goodie>> = prediction
+            .loots
+            .iter()
+            .map(|x| {
+                assert_eq!(x.len(), 1);
+                x[0].2.clone()
+            })
+            .collect();
+        assert_eq!(
+            loots,
+            [
+                vec![],
+                vec![],
+                vec![],
+                vec![Goodie::CommonChest(CommonChest::TaroTuber)],
+                vec![],
+                vec![],
+                vec![
+                    Goodie::RareChest(RareChest::DragontoothShiv),
+                    Goodie::DragonTooth
+                ],
+                vec![],
+                vec![Goodie::DragonTooth],
+                vec![Goodie::RareChest(RareChest::OstrichEgg)]
+            ]
+        );
+    }
+
+    #[test]
+    fn loot_on_its_tiles() {
+        let data = DataPack::builtin();
+        let mut settings = GameSettings::new();
+        settings.seed = 123456789;
+        let mut seen = 0;
+        for days_played in [3, 47, 100] {
+            settings.days_played = days_played;
+            for layout in 1..data.layout_count() as u32 {
+                let floor = generate_floor(settings, 5, layout).unwrap();
+                assert_eq!(floor.loot.len(), floor.loot_pos.len());
+                for ((piece, goodie), &(x, y)) in floor.loot.iter().zip(&floor.loot_pos) {
+                    let &(_, (row, col)) = floor.set_pieces.iter().find(|x| x.0 == *piece).unwrap();
+                    let (px, py, size) = *piece;
+                    let (width, paths) = data.tile_layer(size, "Paths").unwrap();
+                    let src_x = col * size + x - px;
+                    let src_y = row * size + y - py;
+                    assert!((0..size).contains(&(x - px)) && (0..=size).contains(&(y - py)));
+                    let tile = (paths[src_y as usize * width + src_x as usize] & 0x1fff_ffff) - 1;
+                    let want = if *goodie == Goodie::DragonTooth {
+                        335
+                    } else {
+                        332
+                    };
+                    assert_eq!(
+                        tile,
+                        want,
+                        "layout {} day {} at {:?}",
+                        layout,
+                        days_played,
+                        (x, y)
+                    );
+                    seen += 1;
+                }
+            }
+        }
+        assert!(seen > 50, "only {} goodies", seen);
+    }
+
+    #[test]
+    fn cache_matches_fresh_floors() {
+        let mut cache = FloorCache::new();
+        let mut settings_list = vec![];
+        for seed in [1, 123456789, -98765] {
+            for days_played in [5, 47, 200] {
+                for (legacy_rng, cracked_golden_coconut) in
+                    [(false, false), (false, true), (true, false)]
+                {
+                    // the luck buffs only move the luck ranges, so these share their floors
+                    for (max_luck_lvl, special_charm) in [(0, false), (3, true)] {
+                        settings_list.push(GameSettings {
+                            seed,
+                            legacy_rng,
+                            cracked_golden_coconut,
+                            days_played,
+                            max_luck_lvl,
+                            special_charm,
+                            ..GameSettings::new()
+                        });
+                    }
+                }
+            }
+        }
+        // the second time around, every floor comes from the cache
+        for settings in settings_list.iter().chain(&settings_list) {
+            for (minluck, maxluck, lvls) in compute_volcano_layouts(*settings) {
+                for (level, &layout) in lvls.iter().enumerate() {
+                    let level = level as i32;
+                    let fresh = DungeonFloorState::new(
+                        data_pack_for(settings).unwrap(),
+                        *settings,
+                        level,
+                        layout,
+                        minluck,
+                        maxluck,
+                    )
+                    .load_map();
+                    let cached = cache
+                        .loot(*settings, level, layout, minluck, maxluck)
+                        .unwrap();
+                    assert_eq!(
+                        cached, fresh,
+                        "{:?} level {} layout {}",
+                        settings, level, layout
+                    );
+                }
+            }
+            let cached = do_dungeon_cached(*settings, &mut cache).unwrap();
+            let fresh = do_dungeon(*settings).unwrap();
+            assert_eq!(cached.layouts, fresh.layouts);
+            assert_eq!(cached.loots, fresh.loots);
         }
     }
-    out
 }
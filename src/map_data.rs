@@ -1,20 +1,38 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
 
-#[derive(Debug, Copy, Clone)]
-pub enum SetPieceFeature {
-    Rng,
-    Tooth,
-    Chest,
-}
+use crate::game_data::LayoutInfo;
+pub use crate::game_data::{SetPieceFeature, SET_SIZES};
+
+// (rows, cols, events of each variant at row * cols + col)
+type SetPieceSheet = (i32, i32, &'static [&'static [SetPieceFeature]]);
+// (sheet width, [(layer name, gids)]), the gids as in a tmx with the tileset at firstgid 1
+type TileLayers = (usize, &'static [(&'static str, &'static [u32])]);
 
-// (rows, cols)
-pub fn get_piece_sizes(set_size: i32) -> (i32, i32) {
-    include!(concat!(env!("OUT_DIR"), "/set_piece_sizes.rs"))
+/// The map data of one game version, from game_data/.
+pub struct Bundle {
+    /// first game version this data is for
+    pub version: (u32, u32, u32),
+    /// one for each of SET_SIZES
+    pub set_pieces: [SetPieceSheet; 5],
+    /// the art of the set pieces, in the same order
+    pub tile_layers: [TileLayers; 5],
+    /// packed with game_data::pack_tiles
+    pub layouts: &'static [u8],
+    /// [not flipped, flipped] for each layout
+    pub layout_info: &'static [[LayoutInfo; 2]],
 }
 
-// index is (size, row, col)
-//pub fn get_piece_events() -> HashMap<(i32, i32, i32), &'static [SetPieceFeature]> {
-include!(concat!(env!("OUT_DIR"), "/set_piece_events.rs"));
-//}
+// oldest version first
+include!(concat!(env!("OUT_DIR"), "/bundles.rs"));
 
-pub static LAYOUTS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/layouts.bin"));
+pub fn set_size_index(set_size: i32) -> Option<usize> {
+    SET_SIZES.iter().position(|&x| x == set_size)
+}
+
+/// The tiles of a layout packed with `game_data::pack_tiles`, one byte each.
+pub fn unpack_tiles(packed: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    packed.chunks(3).flat_map(|bytes| {
+        let bits = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]);
+        (0..8).map(move |i| (bits >> (3 * i) & 7) as u8)
+    })
+}
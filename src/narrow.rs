@@ -0,0 +1,262 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{f64_next_up, CommonChest, DungeonPrediction, Goodie, ProbabilityRange, RareChest};
+
+/// Something the player saw in-game that only happens for some luck values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Observation {
+    Layout { level: usize, layout: u32 },
+    CommonChest { level: usize, item: CommonChest },
+    RareChest { level: usize, item: RareChest },
+}
+
+/// Sorted, disjoint luck intervals. Like in `ProbabilityRange`, both ends are inclusive and
+/// are in luckMult units.
+pub type LuckSet = Vec<(f64, f64)>;
+
+impl Observation {
+    fn level(&self) -> usize {
+        match *self {
+            Observation::Layout { level, .. }
+            | Observation::CommonChest { level, .. }
+            | Observation::RareChest { level, .. } => level,
+        }
+    }
+
+    fn consistent_luck(&self, prediction: &DungeonPrediction) -> LuckSet {
+        let level = self.level();
+        if level >= 10 {
+            return vec![];
+        }
+        let ranges: Vec<(f64, f64)> = match *self {
+            Observation::Layout { layout, .. } => prediction.layouts[level]
+                .iter()
+                .filter(|x| x.2 == layout)
+                .map(|x| (x.0, x.1))
+                .collect(),
+            Observation::CommonChest { item, .. } => prediction.loots[level]
+                .iter()
+                .filter(|x| x.2.contains(&Goodie::CommonChest(item)))
+                .map(|x| (x.0, x.1))
+                .collect(),
+            Observation::RareChest { item, .. } => prediction.loots[level]
+                .iter()
+                .filter(|x| x.2.contains(&Goodie::RareChest(item)))
+                .map(|x| (x.0, x.1))
+                .collect(),
+        };
+        let mut out: LuckSet = vec![];
+        for (a, b) in ranges {
+            push_range(&mut out, a, b);
+        }
+        out
+    }
+}
+
+pub(crate) fn push_range(set: &mut LuckSet, minluck: f64, maxluck: f64) {
+    if set.last().is_some_and(|x| f64_next_up(x.1) == minluck) {
+        set.last_mut().unwrap().1 = maxluck;
+    } else {
+        set.push((minluck, maxluck));
+    }
+}
+
+fn intersect(a: &[(f64, f64)], b: &[(f64, f64)]) -> LuckSet {
+    let mut out = vec![];
+    for &(a0, a1) in a {
+        for &(b0, b1) in b {
+            let lo = a0.max(b0);
+            let hi = a1.min(b1);
+            if lo <= hi {
+                out.push((lo, hi));
+            }
+        }
+    }
+    out.sort_by(|x, y| x.0.total_cmp(&y.0));
+    out
+}
+
+fn restrict_ranges<T: Clone + PartialEq>(
+    ranges: &ProbabilityRange<T>,
+    luck: &[(f64, f64)],
+) -> ProbabilityRange<T> {
+    let mut out: ProbabilityRange<T> = vec![];
+    for (minluck, maxluck, item) in ranges {
+        for (lo, hi) in intersect(&[(*minluck, *maxluck)], luck) {
+            if out
+                .last()
+                .is_some_and(|y| y.2 == *item && f64_next_up(y.1) == lo)
+            {
+                out.last_mut().unwrap().1 = hi;
+            } else {
+                out.push((lo, hi, item.clone()));
+            }
+        }
+    }
+    out
+}
+
+/// Luck values that are consistent with everything in `observations`. Returns an empty set if
+/// the observations contradict each other.
+pub fn narrow_luck(prediction: &DungeonPrediction, observations: &[Observation]) -> LuckSet {
+    // level 0 is always layout 0, so its single range covers the whole luck interval
+    let mut luck: LuckSet = prediction.layouts[0].iter().map(|x| (x.0, x.1)).collect();
+    for obs in observations {
+        luck = intersect(&luck, &obs.consistent_luck(prediction));
+    }
+    luck
+}
+
+impl DungeonPrediction {
+    /// Only keep the outcomes that can happen for luck values in `luck`.
+    pub fn restrict(&self, luck: &[(f64, f64)]) -> DungeonPrediction {
+        DungeonPrediction {
+            settings: self.settings,
+            layouts: std::array::from_fn(|i| restrict_ranges(&self.layouts[i], luck)),
+            loots: std::array::from_fn(|i| restrict_ranges(&self.loots[i], luck)),
+        }
+    }
+
+    /// Re-filter every floor to what's still possible after `observations`. `None` if no luck
+    /// value matches all of them.
+    pub fn narrow(&self, observations: &[Observation]) -> Option<DungeonPrediction> {
+        let luck = narrow_luck(self, observations);
+        if luck.is_empty() {
+            return None;
+        }
+        Some(self.restrict(&luck))
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct Observations(Vec<Observation>);
+
+#[wasm_bindgen]
+impl Observations {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Default::default()
+    }
+    pub fn add_layout(&mut self, level: usize, layout: u32) {
+        self.0.push(Observation::Layout { level, layout });
+    }
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Observations {
+    pub fn push(&mut self, obs: Observation) {
+        self.0.push(obs);
+    }
+    pub fn as_slice(&self) -> &[Observation] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{do_dungeon, observed_prediction, GameSettings, Language};
+
+    // floor 2 is layout 25 up to the first split and 32 after it, floor 4 has a different chest
+    // in each of three luck ranges
+    fn settings() -> GameSettings {
+        GameSettings {
+            seed: 123456789,
+            days_played: 22,
+            max_luck_lvl: 2,
+            ..GameSettings::new()
+        }
+    }
+
+    const LOW: (f64, f64) = (0.95, 0.968767207567006);
+    const MID: (f64, f64) = (0.9687672075670061, 1.0641154755671116);
+    const HIGH: (f64, f64) = (1.0641154755671118, 1.12);
+
+    #[test]
+    fn by_layout() {
+        let prediction = do_dungeon(settings()).unwrap();
+        assert_eq!(narrow_luck(&prediction, &[]), [(LOW.0, HIGH.1)]);
+        let layout = |layout| Observation::Layout { level: 2, layout };
+        assert_eq!(narrow_luck(&prediction, &[layout(25)]), [LOW]);
+        assert_eq!(narrow_luck(&prediction, &[layout(32)]), [(MID.0, HIGH.1)]);
+        let narrowed = prediction.narrow(&[layout(25)]).unwrap();
+        assert_eq!(narrowed.layouts[2], [(LOW.0, LOW.1, 25)]);
+        assert_eq!(
+            narrowed.loots[4],
+            [(
+                LOW.0,
+                LOW.1,
+                vec![
+                    Goodie::DragonTooth,
+                    Goodie::DragonTooth,
+                    Goodie::CommonChest(CommonChest::ProtectionRing)
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn by_chest() {
+        let prediction = do_dungeon(settings()).unwrap();
+        let common = Observation::CommonChest {
+            level: 4,
+            item: CommonChest::PineappleSeeds,
+        };
+        let rare = Observation::RareChest {
+            level: 4,
+            item: RareChest::PhoenixRing,
+        };
+        assert_eq!(narrow_luck(&prediction, &[common]), [MID]);
+        assert_eq!(narrow_luck(&prediction, &[rare]), [HIGH]);
+        // the layout observation agrees with both, only the chest narrows further
+        let layout = Observation::Layout {
+            level: 2,
+            layout: 32,
+        };
+        assert_eq!(narrow_luck(&prediction, &[layout, common]), [MID]);
+        let narrowed = prediction.narrow(&[layout, rare]).unwrap();
+        assert_eq!(narrowed.layouts[2], [(HIGH.0, HIGH.1, 32)]);
+    }
+
+    #[test]
+    fn contradictions() {
+        let prediction = do_dungeon(settings()).unwrap();
+        let low_layout = Observation::Layout {
+            level: 2,
+            layout: 25,
+        };
+        let rare = Observation::RareChest {
+            level: 4,
+            item: RareChest::PhoenixRing,
+        };
+        let never = Observation::RareChest {
+            level: 4,
+            item: RareChest::OstrichEgg,
+        };
+        assert_eq!(narrow_luck(&prediction, &[low_layout, rare]), []);
+        assert_eq!(narrow_luck(&prediction, &[never]), []);
+        assert!(prediction.narrow(&[low_layout, rare]).is_none());
+
+        // the page then shows everything, with a note
+        let mut observations = Observations::new();
+        observations.push(low_layout);
+        observations.push(rare);
+        let (shown, notes) =
+            observed_prediction(settings(), &observations, Language::German).unwrap();
+        assert_eq!(shown.layouts[2], prediction.layouts[2]);
+        assert_eq!(
+            notes,
+            ["Beobachtungen widersprechen sich, sie werden ignoriert"]
+        );
+        observations.clear();
+        observations.push(rare);
+        let (_, notes) = observed_prediction(settings(), &observations, Language::English).unwrap();
+        assert_eq!(notes, ["observed luck: 0.1282 to 0.2400"]);
+    }
+}
@@ -0,0 +1,360 @@
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    chest_items, do_dungeon, format_date, i18n, DataPackError, DungeonPrediction, GameSettings,
+    Goodie, Language, DRAGON_TOOTH,
+};
+
+/// One trip to the volcano in a `Plan`.
+#[derive(Clone, Debug)]
+pub struct PlannedDay {
+    pub day: u32,
+    /// e.g. "summer 3, Y1"
+    pub date: String,
+    /// the luck buff level to go in with
+    pub luck_lvl: u32,
+    /// the floors that have something wanted on them
+    pub floors: Vec<usize>,
+    /// expected number of each wanted item, by qualified item id
+    pub haul: Vec<(String, f64)>,
+    /// sum of the haul times the items' weights
+    pub value: f64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Plan {
+    /// in day order
+    pub days: Vec<PlannedDay>,
+    pub value: f64,
+}
+
+// what a goodie is, once the chest has been decided
+fn goodie_item(goodie: &Goodie) -> Option<(&'static str, u32)> {
+    match goodie {
+        Goodie::DragonTooth => Some((DRAGON_TOOTH, 1)),
+        Goodie::CommonChest(c) => Some((c.item_id(), c.count())),
+        Goodie::RareChest(c) => Some((c.item_id(), c.count())),
+        Goodie::ChanceChest { .. } => None,
+    }
+}
+
+// the luckMult of every daily luck the game can roll, which are all equally likely
+fn luck_values(settings: GameSettings, luck_lvl: u32) -> impl Iterator<Item = f64> {
+    let charm = if settings.special_charm {
+        0.025_f32 as f64
+    } else {
+        0.
+    };
+    (-100..=100).map(move |x| 1. + luck_lvl as f64 * 0.035 + (x as f64 / 1000. + charm) / 2.)
+}
+
+// expected number of each wanted item on each floor with this luck buff, [floor][wanted item]
+fn expected_haul(
+    prediction: &DungeonPrediction,
+    settings: GameSettings,
+    luck_lvl: u32,
+    wanted: &[(String, f64)],
+) -> Vec<Vec<f64>> {
+    let lucks: Vec<f64> = luck_values(settings, luck_lvl).collect();
+    prediction
+        .loots
+        .iter()
+        .map(|ranges| {
+            let mut haul = vec![0.; wanted.len()];
+            for &luck in &lucks {
+                // the edges of the prediction can be off from these by rounding, those go to
+                // the closest range
+                let range = ranges
+                    .iter()
+                    .find(|x| x.0 <= luck && luck <= x.1)
+                    .unwrap_or(if luck < ranges[0].0 {
+                        &ranges[0]
+                    } else {
+                        &ranges[ranges.len() - 1]
+                    });
+                for (id, count) in range.2.iter().filter_map(goodie_item) {
+                    if let Some(i) = wanted.iter().position(|x| x.0 == id) {
+                        haul[i] += count as f64;
+                    }
+                }
+            }
+            haul.iter().map(|x| x / lucks.len() as f64).collect()
+        })
+        .collect()
+}
+
+/// Builds a `Plan` a few days at a time, keeping only the best `max_trips` days seen so far, so
+/// a long plan can show progress and be cancelled between calls.
+#[wasm_bindgen]
+pub struct TripPlanner {
+    settings: GameSettings,
+    max_trips: usize,
+    wanted: Vec<(String, f64)>,
+    luck_lvls: Vec<u32>,
+    // best first
+    best: Vec<PlannedDay>,
+}
+
+impl TripPlanner {
+    /// See `plan_trips` for what the arguments mean.
+    pub fn with_items(
+        settings: GameSettings,
+        max_trips: usize,
+        wanted: &[(String, f64)],
+        luck_lvls: &[u32],
+    ) -> Self {
+        Self {
+            settings,
+            max_trips,
+            wanted: wanted.to_vec(),
+            luck_lvls: luck_lvls.to_vec(),
+            best: vec![],
+        }
+    }
+
+    // the best luck buff for this day and what it gets, None if nothing wanted can turn up
+    fn plan_day(&self, day: u32, max_lvl: u32) -> Result<Option<PlannedDay>, DataPackError> {
+        let day_settings = GameSettings {
+            days_played: day,
+            max_luck_lvl: max_lvl,
+            ..self.settings
+        };
+        let prediction = do_dungeon(day_settings)?;
+        let best = self
+            .luck_lvls
+            .iter()
+            .map(|&luck_lvl| {
+                let floors = expected_haul(&prediction, day_settings, luck_lvl, &self.wanted);
+                let value: f64 = floors
+                    .iter()
+                    .flat_map(|haul| haul.iter().zip(&self.wanted).map(|(n, w)| n * w.1))
+                    .sum();
+                (luck_lvl, floors, value)
+            })
+            // on a tie, the lower buff is less work
+            .max_by(|a, b| a.2.total_cmp(&b.2).then(b.0.cmp(&a.0)));
+        let (luck_lvl, floors, value) = best.unwrap();
+        if value <= 0. {
+            return Ok(None);
+        }
+        let haul = self
+            .wanted
+            .iter()
+            .enumerate()
+            .map(|(i, (id, _))| (id.clone(), floors.iter().map(|x| x[i]).sum()))
+            .collect();
+        Ok(Some(PlannedDay {
+            day,
+            date: format_date(day),
+            luck_lvl,
+            floors: (0..floors.len())
+                .filter(|&i| floors[i].iter().any(|&x| x > 0.))
+                .collect(),
+            haul,
+            value,
+        }))
+    }
+
+    /// Consider the days in `days` too.
+    pub fn add_days(&mut self, days: RangeInclusive<u32>) -> Result<(), DataPackError> {
+        let Some(&max_lvl) = self.luck_lvls.iter().max() else {
+            return Ok(());
+        };
+        for day in *days.start().max(&1)..=*days.end() {
+            if let Some(planned) = self.plan_day(day, max_lvl)? {
+                self.best.push(planned);
+            }
+        }
+        // days don't affect each other, so the best days are simply the ones worth the most
+        self.best
+            .sort_by(|a, b| b.value.total_cmp(&a.value).then(a.day.cmp(&b.day)));
+        self.best.truncate(self.max_trips);
+        Ok(())
+    }
+
+    /// The plan for the days added so far.
+    pub fn plan(&self) -> Plan {
+        let mut days = self.best.clone();
+        days.sort_by_key(|x| x.day);
+        Plan {
+            value: days.iter().map(|x| x.value).sum(),
+            days,
+        }
+    }
+}
+
+/// Pick the `max_trips` days in `days` with the best expected haul of the `wanted` (qualified
+/// item id, weight) items, going in with whichever of `luck_lvls` is best on that day. Daily luck
+/// isn't known ahead, so every day's luck counts as equally likely. Items count once each, so a
+/// second phoenix ring is worth as much as the first.
+pub fn plan_trips(
+    settings: GameSettings,
+    days: RangeInclusive<u32>,
+    max_trips: usize,
+    wanted: &[(String, f64)],
+    luck_lvls: &[u32],
+) -> Result<Plan, DataPackError> {
+    let mut planner = TripPlanner::with_items(settings, max_trips, wanted, luck_lvls);
+    planner.add_days(days)?;
+    Ok(planner.plan())
+}
+
+fn haul_text<'a>(lang: Language, haul: impl Iterator<Item = (&'a str, f64)>) -> String {
+    haul.filter(|x| x.1 > 0.)
+        .map(|(id, n)| format!("{} {:.2}", i18n::tr(lang, id), n))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Everything a trip can find, as [qualified item id, name] pairs.
+#[wasm_bindgen]
+pub fn loot_items(lang: Language) -> js_sys::Array {
+    let items = chest_items(lang);
+    let tooth: js_sys::Array = [DRAGON_TOOTH, i18n::tr(lang, DRAGON_TOOTH)]
+        .into_iter()
+        .map(JsValue::from)
+        .collect();
+    items.push(&tooth);
+    items
+}
+
+#[wasm_bindgen]
+impl TripPlanner {
+    /// `wanted` and `weights` go together.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        settings: GameSettings,
+        max_trips: u32,
+        wanted: Vec<String>,
+        weights: Vec<f64>,
+        luck_lvls: Vec<u32>,
+    ) -> TripPlanner {
+        let wanted: Vec<(String, f64)> = wanted.into_iter().zip(weights).collect();
+        TripPlanner::with_items(settings, max_trips as usize, &wanted, &luck_lvls)
+    }
+
+    #[wasm_bindgen(js_name = add_days)]
+    pub fn js_add_days(&mut self, first_day: u32, last_day: u32) -> Result<(), JsError> {
+        Ok(self.add_days(first_day..=last_day)?)
+    }
+
+    /// One line of text per trip, and the total expected haul last.
+    pub fn lines(&self, lang: Language) -> js_sys::Array {
+        let plan = self.plan();
+        let mut lines = vec![];
+        for day in &plan.days {
+            let mut line = format!(
+                "{} (day {}), luck buff {}, floors ",
+                day.date, day.day, day.luck_lvl
+            );
+            let floors: Vec<String> = day.floors.iter().map(|x| x.to_string()).collect();
+            write!(
+                line,
+                "{}: {}",
+                floors.join(", "),
+                haul_text(lang, day.haul.iter().map(|x| (&x.0[..], x.1)))
+            )
+            .unwrap();
+            lines.push(line);
+        }
+        let total = self.wanted.iter().enumerate().map(|(i, (id, _))| {
+            let n: f64 = plan.days.iter().map(|x| x.haul[i].1).sum();
+            (&id[..], n)
+        });
+        lines.push(format!("expected in total: {}", haul_text(lang, total)));
+        lines.into_iter().map(JsValue::from).collect()
+    }
+}
+
+/// `plan_trips` for the page in one go, see `TripPlanner` for the arguments and result.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn plan_days(
+    settings: GameSettings,
+    first_day: u32,
+    last_day: u32,
+    max_trips: u32,
+    wanted: Vec<String>,
+    weights: Vec<f64>,
+    luck_lvls: Vec<u32>,
+    lang: Language,
+) -> Result<js_sys::Array, JsError> {
+    let mut planner = TripPlanner::new(settings, max_trips, wanted, weights, luck_lvls);
+    planner.add_days(first_day..=last_day)?;
+    Ok(planner.lines(lang))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_match_one_go() {
+        let mut settings = GameSettings::new();
+        settings.seed = 123456789;
+        let wanted = [("(O)852".to_string(), 1.), ("(B)855".to_string(), 5.)];
+        let whole = plan_trips(settings, 1..=40, 3, &wanted, &[0, 2]).unwrap();
+        let mut planner = TripPlanner::with_items(settings, 3, &wanted, &[0, 2]);
+        for first in (1..=40).step_by(7) {
+            planner.add_days(first..=(first + 6).min(40)).unwrap();
+        }
+        let chunked = planner.plan();
+        assert_eq!(whole.days.len(), 3);
+        assert_eq!(
+            whole.days.iter().map(|x| x.day).collect::<Vec<_>>(),
+            chunked.days.iter().map(|x| x.day).collect::<Vec<_>>()
+        );
+        assert_eq!(whole.value, chunked.value);
+    }
+
+    #[test]
+    fn haul_by_hand() {
+        // floor 4 has 2 teeth and a protection ring up to luckMult 0.96877, pineapple seeds (5)
+        // up to 1.06412 and a phoenix ring above. floor 8 always has 3 teeth.
+        let settings = GameSettings {
+            seed: 123456789,
+            days_played: 22,
+            max_luck_lvl: 3,
+            ..GameSettings::new()
+        };
+        let wanted: Vec<(String, f64)> = ["(O)863", "(O)833", "(O)861", "(O)852"]
+            .into_iter()
+            .map(|x| (x.to_string(), 1.))
+            .collect();
+        let haul = |settings, luck_lvl| -> Vec<Vec<f64>> {
+            expected_haul(&do_dungeon(settings).unwrap(), settings, luck_lvl, &wanted)
+        };
+        let nothing = vec![0.; 4];
+        let floors = |floor_4: [f64; 4]| {
+            let mut out = vec![nothing.clone(); 10];
+            out[4] = floor_4.iter().map(|x| x / 201.).collect();
+            out[8][3] = 3.;
+            out
+        };
+
+        // no buff: luckMult 1 + x / 2000. seeds from x = -62 on, below that the ring and teeth
+        assert_eq!(haul(settings, 0), floors([0., 163. * 5., 38., 38. * 2.]));
+        // +0.035 per level: 1.07 + x / 2000. the phoenix ring from x = -11 on
+        assert_eq!(haul(settings, 2), floors([112., 89. * 5., 0., 0.]));
+        // the charm adds half of 0.025 as an f32: 1.0125 + x / 2000, seeds from x = -87 on
+        let charm = GameSettings {
+            special_charm: true,
+            ..settings
+        };
+        assert_eq!(haul(charm, 0), floors([0., 188. * 5., 13., 13. * 2.]));
+        // 1.1175 + x / 2000 is past the last split for every x
+        assert_eq!(haul(charm, 3), floors([201., 0., 0., 0.]));
+
+        // the phoenix ring is worth more than the seeds that level 2 gives up for it
+        let wanted = [("(O)863".to_string(), 10.), ("(O)833".to_string(), 1.)];
+        let plan = plan_trips(settings, 22..=22, 1, &wanted, &[0, 2]).unwrap();
+        let day = &plan.days[0];
+        assert_eq!((day.day, day.luck_lvl, &day.floors[..]), (22, 2, &[4][..]));
+        assert_eq!(day.haul[0].1, 112. / 201.);
+        assert_eq!(day.haul[1].1, 445. / 201.);
+        assert_eq!(plan.value, 1565. / 201.);
+    }
+}
@@ -0,0 +1,361 @@
+use std::fmt::Write;
+
+use base64::Engine;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    display_luck, format_date, group_loot, is_monster_floor, is_mushroom_floor,
+    observed_prediction, render_map_svg, DataPackError, DungeonPrediction, GameSettings,
+    GameVersion, Language, Observations,
+};
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// describe a luck range relative to the full range of the prediction
+fn luck_words(minluck: f64, maxluck: f64, bounds: (f64, f64)) -> String {
+    match (minluck <= bounds.0, maxluck >= bounds.1) {
+        (true, true) => "at any luck".to_string(),
+        (true, false) => format!("if your luck is at most {:.4}", display_luck(maxluck)),
+        (false, true) => format!("if your luck is at least {:.4}", display_luck(minluck)),
+        (false, false) => format!(
+            "if your luck is between {:.4} and {:.4}",
+            display_luck(minluck),
+            display_luck(maxluck)
+        ),
+    }
+}
+
+fn layout_name(layout: u32) -> String {
+    if is_mushroom_floor(layout) {
+        format!("{} (mushroom floor)", layout)
+    } else if is_monster_floor(layout) {
+        format!("{} (monster floor)", layout)
+    } else {
+        layout.to_string()
+    }
+}
+
+fn settings_summary(settings: &GameSettings) -> String {
+    let mut parts = vec![
+        format!("seed {}", settings.seed),
+        format!("day {}", settings.days_played),
+    ];
+    if settings.legacy_rng {
+        parts.push("legacy rng".to_string());
+    }
+    match settings.version {
+        GameVersion::V1_5 => parts.push("version 1.5".to_string()),
+        GameVersion::V1_6 => {}
+        GameVersion::V1_6_4 => parts.push("version 1.6.4 or above".to_string()),
+    }
+    if settings.has_caldera {
+        parts.push("reached caldera".to_string());
+    }
+    if settings.cracked_golden_coconut {
+        parts.push("cracked golden coconut".to_string());
+    }
+    if settings.special_charm {
+        parts.push("special charm".to_string());
+    }
+    parts.push(format!("luck buffs up to level {}", settings.max_luck_lvl));
+    parts.join(", ")
+}
+
+struct Writer {
+    format: ReportFormat,
+    out: String,
+}
+
+impl Writer {
+    fn heading(&mut self, level: usize, text: &str) {
+        match self.format {
+            ReportFormat::Markdown => {
+                writeln!(self.out, "{} {}\n", "#".repeat(level), text).unwrap()
+            }
+            ReportFormat::Html => {
+                writeln!(self.out, "<h{level}>{}</h{level}>", escape_html(text)).unwrap()
+            }
+        }
+    }
+
+    fn paragraph(&mut self, text: &str) {
+        match self.format {
+            ReportFormat::Markdown => writeln!(self.out, "{}\n", text).unwrap(),
+            ReportFormat::Html => writeln!(self.out, "<p>{}</p>", escape_html(text)).unwrap(),
+        }
+    }
+
+    fn list(&mut self, items: &[String]) {
+        match self.format {
+            ReportFormat::Markdown => {
+                for item in items {
+                    writeln!(self.out, "- {}", item).unwrap();
+                }
+                self.out += "\n";
+            }
+            ReportFormat::Html => {
+                self.out += "<ul>\n";
+                for item in items {
+                    writeln!(self.out, "<li>{}</li>", escape_html(item)).unwrap();
+                }
+                self.out += "</ul>\n";
+            }
+        }
+    }
+
+    fn table(&mut self, header: &[&str], rows: &[Vec<String>]) {
+        match self.format {
+            ReportFormat::Markdown => {
+                writeln!(self.out, "| {} |", header.join(" | ")).unwrap();
+                writeln!(self.out, "|{}", "---|".repeat(header.len())).unwrap();
+                for row in rows {
+                    writeln!(self.out, "| {} |", row.join(" | ")).unwrap();
+                }
+                self.out += "\n";
+            }
+            ReportFormat::Html => {
+                self.out += "<table>\n<tr>";
+                for cell in header {
+                    write!(self.out, "<th>{}</th>", escape_html(cell)).unwrap();
+                }
+                self.out += "</tr>\n";
+                for row in rows {
+                    self.out += "<tr>";
+                    for cell in row {
+                        write!(self.out, "<td>{}</td>", escape_html(cell)).unwrap();
+                    }
+                    self.out += "</tr>\n";
+                }
+                self.out += "</table>\n";
+            }
+        }
+    }
+
+    fn svg(&mut self, alt: &str, svg: &str) {
+        match self.format {
+            ReportFormat::Markdown => {
+                let data = base64::engine::general_purpose::STANDARD.encode(svg);
+                writeln!(self.out, "![{}](data:image/svg+xml;base64,{})\n", alt, data).unwrap();
+            }
+            ReportFormat::Html => writeln!(self.out, "<figure>{}</figure>", svg).unwrap(),
+        }
+    }
+}
+
+/// A complete document with everything the page shows for this day: the layouts, the loot
+/// with the luck needed for it in words, and a map of every floor that can come up.
+pub fn generate_report(
+    prediction: &DungeonPrediction,
+    notes: &[String],
+    format: ReportFormat,
+) -> Result<String, DataPackError> {
+    let settings = prediction.settings;
+    let title = format!("Volcano dungeon, {}", format_date(settings.days_played));
+    let mut w = Writer {
+        format,
+        out: String::new(),
+    };
+    if format == ReportFormat::Html {
+        writeln!(
+            w.out,
+            "<!doctype html>\n<html>\n<head>\n<meta charset=\"utf-8\" />\n<title>{}</title>\n\
+             <style>body {{ font-family: sans-serif; }} \
+             table {{ border-collapse: collapse; }} \
+             td, th {{ border: 1px solid black; padding: 2px 6px; }} \
+             figure svg {{ max-width: 100%; height: auto; }}</style>\n</head>\n<body>",
+            escape_html(&title)
+        )
+        .unwrap();
+    }
+    w.heading(1, &title);
+    w.paragraph(&settings_summary(&settings));
+    for note in notes {
+        w.paragraph(note);
+    }
+
+    let full = &prediction.layouts[0];
+    let bounds = (full[0].0, full[full.len() - 1].1);
+
+    w.heading(2, "Layouts");
+    let mut rows = vec![];
+    for (lvl, layouts) in prediction.layouts.iter().enumerate() {
+        for &(minluck, maxluck, layout) in layouts {
+            rows.push(vec![
+                lvl.to_string(),
+                layout_name(layout),
+                luck_words(minluck, maxluck, bounds),
+            ]);
+        }
+    }
+    w.table(&["floor", "layout", "when"], &rows);
+
+    w.heading(2, "Loot");
+    let mut any_loot = false;
+    for (lvl, loots) in prediction.loots.iter().enumerate() {
+        if loots.iter().all(|x| x.2.is_empty()) {
+            continue;
+        }
+        any_loot = true;
+        w.heading(3, &format!("Floor {}", lvl));
+        for (minluck, maxluck, loot) in loots {
+            if loots.len() > 1 {
+                let mut when = luck_words(*minluck, *maxluck, bounds);
+                when[..1].make_ascii_uppercase();
+                w.paragraph(&format!("{}:", when));
+            }
+            let mut items: Vec<String> = group_loot(loot)
+                .into_iter()
+                .map(|(goodie, count)| {
+                    if count > 1 {
+                        format!("{} ({})", goodie, count)
+                    } else {
+                        goodie.to_string()
+                    }
+                })
+                .collect();
+            if items.is_empty() {
+                items.push("nothing".to_string());
+            }
+            w.list(&items);
+        }
+    }
+    if !any_loot {
+        w.paragraph("No dragon teeth or chests on any floor.");
+    }
+
+    w.heading(2, "Maps");
+    for (lvl, layouts) in prediction.layouts.iter().enumerate() {
+        let mut seen = vec![];
+        for &(_, _, layout) in layouts {
+            if seen.contains(&layout) {
+                continue;
+            }
+            seen.push(layout);
+            let name = format!("Floor {}, layout {}", lvl, layout_name(layout));
+            w.heading(3, &name);
+            w.svg(&name, &render_map_svg(settings, lvl as i32, layout, 6)?);
+        }
+    }
+
+    if format == ReportFormat::Html {
+        w.out += "</body>\n</html>\n";
+    }
+    Ok(w.out)
+}
+
+#[wasm_bindgen]
+pub fn report(
+    settings: GameSettings,
+    observations: &Observations,
+    format: ReportFormat,
+) -> Result<String, JsError> {
+    let (prediction, notes) = observed_prediction(settings, observations, Language::English)?;
+    Ok(generate_report(&prediction, &notes, format)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::do_dungeon;
+
+    fn prediction() -> DungeonPrediction {
+        do_dungeon(GameSettings {
+            seed: 123456789,
+            days_played: 22,
+            max_luck_lvl: 2,
+            ..GameSettings::new()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn markdown_day() {
+        let notes = ["a <note>".to_string()];
+        let report = generate_report(&prediction(), &notes, ReportFormat::Markdown).unwrap();
+        let (text, maps) = report.split_at(report.find("## Maps").unwrap());
+        assert_eq!(
+            text,
+            "\
+# Volcano dungeon, spring 22, Y1
+
+seed 123456789, day 22, luck buffs up to level 2
+
+a <note>
+
+## Layouts
+
+| floor | layout | when |
+|---|---|---|
+| 0 | 0 | at any luck |
+| 1 | 15 | at any luck |
+| 2 | 25 | if your luck is at most -0.0625 |
+| 2 | 32 (mushroom floor) | if your luck is at least -0.0625 |
+| 3 | 10 | at any luck |
+| 4 | 28 | if your luck is at most -0.0625 |
+| 4 | 24 | if your luck is at least -0.0625 |
+| 5 | 31 | at any luck |
+| 6 | 12 | at any luck |
+| 7 | 1 | at any luck |
+| 8 | 4 | at any luck |
+| 9 | 30 | at any luck |
+
+## Loot
+
+### Floor 4
+
+If your luck is at most -0.0625:
+
+- Dragon Tooth (2)
+- common chest: Protection Ring
+
+If your luck is between -0.0625 and 0.1282:
+
+- common chest: Pineapple Seeds (5)
+
+If your luck is at least 0.1282:
+
+- rare chest: Phoenix Ring
+
+### Floor 8
+
+- Dragon Tooth (3)
+
+### Floor 9
+
+- rare chest: Mermaid Boots
+
+"
+        );
+        // one map for every layout a floor can have
+        let headings: Vec<_> = maps.lines().filter(|x| x.starts_with("### ")).collect();
+        assert_eq!(headings.len(), 12);
+        assert_eq!(headings[3], "### Floor 2, layout 32 (mushroom floor)");
+        let images = maps.matches("](data:image/svg+xml;base64,").count();
+        assert_eq!(images, 12);
+    }
+
+    #[test]
+    fn html_day() {
+        let notes = ["a <note>".to_string()];
+        let report = generate_report(&prediction(), &notes, ReportFormat::Html).unwrap();
+        assert!(report.starts_with("<!doctype html>\n"));
+        assert!(report.ends_with("</body>\n</html>\n"));
+        assert!(report.contains("<p>a &lt;note&gt;</p>\n"));
+        assert!(report.contains(
+            "<tr><td>2</td><td>32 (mushroom floor)</td><td>if your luck is at least -0.0625</td></tr>\n"
+        ));
+        assert!(report.contains("<p>If your luck is between -0.0625 and 0.1282:</p>\n<ul>\n<li>common chest: Pineapple Seeds (5)</li>\n</ul>\n"));
+        assert_eq!(report.matches("<figure><svg").count(), 12);
+    }
+}
@@ -0,0 +1,335 @@
+use std::fmt::Display;
+
+use roxmltree::{Document, Node};
+use wasm_bindgen::prelude::*;
+
+use crate::{GameSettings, GameVersion};
+
+#[derive(Debug)]
+pub enum SaveError {
+    Xml(roxmltree::Error),
+    UnknownRoot(String),
+    BadValue { field: &'static str, value: String },
+}
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Xml(e) => write!(f, "not a valid xml file: {}", e),
+            SaveError::UnknownRoot(name) => write!(
+                f,
+                "expected a save file or SaveGameInfo, found a <{}> document",
+                name
+            ),
+            SaveError::BadValue { field, value } => {
+                write!(f, "unexpected value for {}: {:?}", field, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<roxmltree::Error> for SaveError {
+    fn from(e: roxmltree::Error) -> Self {
+        SaveError::Xml(e)
+    }
+}
+
+fn child<'a, 'i>(node: Node<'a, 'i>, name: &str) -> Option<Node<'a, 'i>> {
+    node.children().find(|x| x.has_tag_name(name))
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name).map(|x| x.text().unwrap_or("").trim())
+}
+
+fn parse_field<T: std::str::FromStr>(field: &'static str, value: &str) -> Result<T, SaveError> {
+    value.parse().map_err(|_| SaveError::BadValue {
+        field,
+        value: value.to_string(),
+    })
+}
+
+fn parse_bool(field: &'static str, node: Node, name: &str) -> Result<Option<bool>, SaveError> {
+    child_text(node, name)
+        .map(|x| parse_field(field, x))
+        .transpose()
+}
+
+// (major, minor, patch), anything after the numbers (e.g. beta tags) is ignored
+fn parse_version(version: &str) -> Result<(u32, u32, u32), SaveError> {
+    let bad = || SaveError::BadValue {
+        field: "gameVersion",
+        value: version.to_string(),
+    };
+    let mut parts = version.split('.').map(|x| {
+        let digits = x.find(|c: char| !c.is_ascii_digit()).unwrap_or(x.len());
+        x[..digits].parse::<u32>()
+    });
+    let major = parts.next().ok_or_else(bad)?.map_err(|_| bad())?;
+    let minor = parts.next().unwrap_or(Ok(0)).map_err(|_| bad())?;
+    let patch = parts.next().unwrap_or(Ok(0)).map_err(|_| bad())?;
+    Ok((major, minor, patch))
+}
+
+fn days_played_from_stats(farmer: Node) -> Result<Option<u32>, SaveError> {
+    let Some(stats) = child(farmer, "stats") else {
+        return Ok(None);
+    };
+    // 1.6 moved most stats into a string -> uint dictionary
+    if let Some(values) = child(stats, "Values") {
+        for item in values.children().filter(|x| x.has_tag_name("item")) {
+            let key = child(item, "key").and_then(|x| child_text(x, "string"));
+            if key == Some("daysPlayed") {
+                let value = child(item, "value").and_then(|x| child_text(x, "unsignedInt"));
+                return value.map(|x| parse_field("daysPlayed", x)).transpose();
+            }
+        }
+    }
+    child_text(stats, "daysPlayed")
+        .or_else(|| child_text(stats, "DaysPlayed"))
+        .map(|x| parse_field("daysPlayed", x))
+        .transpose()
+}
+
+fn days_played_from_date(season: u32, day: u32, year: u32) -> u32 {
+    (year.max(1) - 1) * 112 + season * 28 + day
+}
+
+fn season_index(season: &str) -> Result<u32, SaveError> {
+    match season.to_ascii_lowercase().as_str() {
+        "spring" | "0" => Ok(0),
+        "summer" | "1" => Ok(1),
+        "fall" | "2" => Ok(2),
+        "winter" | "3" => Ok(3),
+        _ => Err(SaveError::BadValue {
+            field: "season",
+            value: season.to_string(),
+        }),
+    }
+}
+
+fn apply_farmer(farmer: Node, settings: &mut GameSettings) -> Result<(), SaveError> {
+    if let Some(mail) = child(farmer, "mailReceived") {
+        settings.has_caldera = mail
+            .children()
+            .any(|x| x.has_tag_name("string") && x.text() == Some("reachedCaldera"));
+    }
+    if let Some(charm) = parse_bool("hasSpecialCharm", farmer, "hasSpecialCharm")? {
+        settings.special_charm = charm;
+    }
+    if let Some(days) = days_played_from_stats(farmer)? {
+        settings.days_played = days;
+    }
+    Ok(())
+}
+
+fn apply_version(root: Node, settings: &mut GameSettings) -> Result<(), SaveError> {
+    // saves from before 1.5 don't store a version at all
+    let version = match child_text(root, "gameVersion") {
+        Some(v) if !v.is_empty() => parse_version(v)?,
+        _ => (1, 5, 0),
+    };
+    settings.version = if version >= (1, 6, 4) {
+        GameVersion::V1_6_4
+    } else if version >= (1, 6, 0) {
+        GameVersion::V1_6
+    } else {
+        GameVersion::V1_5
+    };
+    if version < (1, 6, 0) {
+        settings.legacy_rng = true;
+    } else if let Some(legacy) = parse_bool("useLegacyRandom", root, "useLegacyRandom")? {
+        settings.legacy_rng = legacy;
+    }
+    Ok(())
+}
+
+/// Fill in `settings` from the contents of a save file. Both the main save (`<SaveGame>`) and
+/// `SaveGameInfo` (`<Farmer>`) are accepted. `SaveGameInfo` doesn't contain the game seed, the
+/// golden coconut flag or (for 1.6 saves) the legacy random setting, so those are left as they
+/// were.
+pub fn parse_save(xml: &str, settings: &mut GameSettings) -> Result<(), SaveError> {
+    let doc = Document::parse(xml)?;
+    let root = doc.root_element();
+    match root.tag_name().name() {
+        "SaveGame" => {
+            apply_version(root, settings)?;
+            if let Some(id) = child_text(root, "uniqueIDForThisGame") {
+                // the id is a ulong, and the game halves it before mixing it into seeds. that's
+                // the same as halving it as an int only when it fits in one, which the ids the
+                // game generates always do
+                let id = parse_field::<u64>("uniqueIDForThisGame", id)?;
+                settings.seed = i32::try_from(id).map_err(|_| SaveError::BadValue {
+                    field: "uniqueIDForThisGame",
+                    value: id.to_string(),
+                })?;
+            }
+            if let Some(cracked) = parse_bool("goldenCoconutCracked", root, "goldenCoconutCracked")?
+            {
+                settings.cracked_golden_coconut = cracked;
+            }
+            if let (Some(season), Some(day), Some(year)) = (
+                child_text(root, "currentSeason"),
+                child_text(root, "dayOfMonth"),
+                child_text(root, "year"),
+            ) {
+                settings.days_played = days_played_from_date(
+                    season_index(season)?,
+                    parse_field("dayOfMonth", day)?,
+                    parse_field("year", year)?,
+                );
+            }
+            if let Some(player) = child(root, "player") {
+                apply_farmer(player, settings)?;
+            }
+        }
+        "Farmer" => {
+            apply_version(root, settings)?;
+            if let (Some(season), Some(day), Some(year)) = (
+                child_text(root, "seasonForSaveGame"),
+                child_text(root, "dayOfMonthForSaveGame"),
+                child_text(root, "yearForSaveGame"),
+            ) {
+                settings.days_played = days_played_from_date(
+                    season_index(season)?,
+                    parse_field("dayOfMonthForSaveGame", day)?,
+                    parse_field("yearForSaveGame", year)?,
+                );
+            }
+            apply_farmer(root, settings)?;
+        }
+        other => return Err(SaveError::UnknownRoot(other.to_string())),
+    }
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub fn import_save(settings: GameSettings, xml: &str) -> Result<GameSettings, JsError> {
+    let mut settings = settings;
+    parse_save(xml, &mut settings)?;
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAVE_1_5: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<SaveGame xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <player>
+    <mailReceived>
+      <string>ccDoorUnlock</string>
+      <string>reachedCaldera</string>
+    </mailReceived>
+    <hasSpecialCharm>true</hasSpecialCharm>
+    <stats>
+      <daysPlayed>143</daysPlayed>
+    </stats>
+  </player>
+  <currentSeason>summer</currentSeason>
+  <dayOfMonth>3</dayOfMonth>
+  <year>2</year>
+  <uniqueIDForThisGame>123456789</uniqueIDForThisGame>
+  <goldenCoconutCracked>true</goldenCoconutCracked>
+  <gameVersion>1.5.6</gameVersion>
+</SaveGame>"#;
+
+    const SAVE_1_6: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<SaveGame xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <player>
+    <mailReceived>
+      <string>ccDoorUnlock</string>
+    </mailReceived>
+    <hasSpecialCharm>false</hasSpecialCharm>
+    <stats>
+      <Values>
+        <item>
+          <key><string>stepsTaken</string></key>
+          <value><unsignedInt>40000</unsignedInt></value>
+        </item>
+        <item>
+          <key><string>daysPlayed</string></key>
+          <value><unsignedInt>58</unsignedInt></value>
+        </item>
+      </Values>
+    </stats>
+  </player>
+  <currentSeason>winter</currentSeason>
+  <dayOfMonth>1</dayOfMonth>
+  <year>1</year>
+  <uniqueIDForThisGame>987654321</uniqueIDForThisGame>
+  <goldenCoconutCracked>false</goldenCoconutCracked>
+  <gameVersion>1.6.8</gameVersion>
+  <useLegacyRandom>false</useLegacyRandom>
+</SaveGame>"#;
+
+    const SAVE_GAME_INFO: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<Farmer xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <mailReceived>
+    <string>reachedCaldera</string>
+  </mailReceived>
+  <hasSpecialCharm>false</hasSpecialCharm>
+  <seasonForSaveGame>1</seasonForSaveGame>
+  <dayOfMonthForSaveGame>12</dayOfMonthForSaveGame>
+  <yearForSaveGame>3</yearForSaveGame>
+  <gameVersion>1.6.9</gameVersion>
+</Farmer>"#;
+
+    #[test]
+    fn save_1_5() {
+        let mut settings = GameSettings::new();
+        parse_save(SAVE_1_5, &mut settings).unwrap();
+        assert_eq!(settings.seed, 123456789);
+        assert!(settings.legacy_rng);
+        assert_eq!(settings.version, GameVersion::V1_5);
+        assert!(settings.has_caldera);
+        assert!(settings.special_charm);
+        assert!(settings.cracked_golden_coconut);
+        assert_eq!(settings.days_played, 143);
+    }
+
+    #[test]
+    fn save_1_6_stats_dictionary() {
+        let mut settings = GameSettings::new();
+        settings.legacy_rng = true;
+        settings.has_caldera = true;
+        parse_save(SAVE_1_6, &mut settings).unwrap();
+        assert_eq!(settings.seed, 987654321);
+        assert!(!settings.legacy_rng);
+        assert_eq!(settings.version, GameVersion::V1_6_4);
+        assert!(!settings.has_caldera);
+        assert!(!settings.special_charm);
+        assert!(!settings.cracked_golden_coconut);
+        assert_eq!(settings.days_played, 58);
+    }
+
+    #[test]
+    fn save_game_info_keeps_the_rest() {
+        let mut settings = GameSettings::new();
+        settings.seed = 42;
+        settings.legacy_rng = true;
+        settings.cracked_golden_coconut = true;
+        parse_save(SAVE_GAME_INFO, &mut settings).unwrap();
+        assert_eq!(settings.seed, 42);
+        assert!(settings.legacy_rng);
+        assert!(settings.cracked_golden_coconut);
+        assert!(settings.has_caldera);
+        assert_eq!(settings.version, GameVersion::V1_6_4);
+        assert_eq!(settings.days_played, 2 * 112 + 28 + 12);
+    }
+
+    #[test]
+    fn id_out_of_range() {
+        let xml = SAVE_1_6.replace("987654321", "4294967296");
+        let err = parse_save(&xml, &mut GameSettings::new()).unwrap_err();
+        assert!(matches!(
+            err,
+            SaveError::BadValue {
+                field: "uniqueIDForThisGame",
+                ..
+            }
+        ));
+    }
+}
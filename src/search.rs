@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::GameSettings;
+
+// how many seeds or days a thread takes at once. small enough that progress updates come often,
+// big enough that handing out chunks doesn't matter next to generating the dungeons.
+const CHUNK_SIZE: u64 = 1024;
+
+fn all_cores() -> usize {
+    thread::available_parallelism().map_or(1, |x| x.get())
+}
+
+// runs `check` on every number in first..=last on `num_threads` threads. the results come back
+// in order no matter which thread finished first, and `progress` runs on the calling thread so
+// it doesn't need to be Send.
+fn par_filter_map<T: Send>(
+    num_threads: usize,
+    first: i64,
+    last: i64,
+    check: impl Fn(i64) -> Option<T> + Sync,
+    mut progress: impl FnMut(u64, u64),
+) -> Vec<(i64, T)> {
+    if last < first {
+        return vec![];
+    }
+    let total = (last - first + 1) as u64;
+    let num_chunks = total.div_ceil(CHUNK_SIZE);
+    let next_chunk = AtomicU64::new(0);
+    let mut found = BTreeMap::new();
+    let mut done = 0;
+    thread::scope(|s| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..(num_threads as u64).min(num_chunks) {
+            let tx = tx.clone();
+            let (next_chunk, check) = (&next_chunk, &check);
+            s.spawn(move || loop {
+                let chunk = next_chunk.fetch_add(1, Ordering::Relaxed);
+                if chunk >= num_chunks {
+                    break;
+                }
+                let start = first + (chunk * CHUNK_SIZE) as i64;
+                let end = (start + CHUNK_SIZE as i64 - 1).min(last);
+                let hits: Vec<_> = (start..=end)
+                    .filter_map(|i| check(i).map(|x| (i, x)))
+                    .collect();
+                if tx.send((chunk, (end - start + 1) as u64, hits)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+        for (chunk, len, hits) in rx {
+            done += len;
+            progress(done, total);
+            found.insert(chunk, hits);
+        }
+    });
+    found.into_values().flatten().collect()
+}
+
+/// Run `check` for every seed in `seeds`, spread over all CPU cores, and return the seeds it
+/// returned something for, in seed order. `progress` gets called with (seeds checked, total)
+/// as the search goes.
+pub fn search_seeds<T: Send>(
+    settings: GameSettings,
+    seeds: RangeInclusive<i32>,
+    check: impl Fn(GameSettings) -> Option<T> + Sync,
+    progress: impl FnMut(u64, u64),
+) -> Vec<(i32, T)> {
+    search_seeds_on(all_cores(), settings, seeds, check, progress)
+}
+
+fn search_seeds_on<T: Send>(
+    num_threads: usize,
+    settings: GameSettings,
+    seeds: RangeInclusive<i32>,
+    check: impl Fn(GameSettings) -> Option<T> + Sync,
+    progress: impl FnMut(u64, u64),
+) -> Vec<(i32, T)> {
+    let found = par_filter_map(
+        num_threads,
+        *seeds.start() as i64,
+        *seeds.end() as i64,
+        |seed| {
+            check(GameSettings {
+                seed: seed as i32,
+                ..settings
+            })
+        },
+        progress,
+    );
+    found.into_iter().map(|(x, t)| (x as i32, t)).collect()
+}
+
+/// Same as `search_seeds`, but over days played on one save.
+pub fn search_days<T: Send>(
+    settings: GameSettings,
+    days: RangeInclusive<u32>,
+    check: impl Fn(GameSettings) -> Option<T> + Sync,
+    progress: impl FnMut(u64, u64),
+) -> Vec<(u32, T)> {
+    search_days_on(all_cores(), settings, days, check, progress)
+}
+
+fn search_days_on<T: Send>(
+    num_threads: usize,
+    settings: GameSettings,
+    days: RangeInclusive<u32>,
+    check: impl Fn(GameSettings) -> Option<T> + Sync,
+    progress: impl FnMut(u64, u64),
+) -> Vec<(u32, T)> {
+    let found = par_filter_map(
+        num_threads,
+        *days.start() as i64,
+        *days.end() as i64,
+        |day| {
+            check(GameSettings {
+                days_played: day as u32,
+                ..settings
+            })
+        },
+        progress,
+    );
+    found.into_iter().map(|(x, t)| (x as u32, t)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_volcano_layouts;
+
+    // seeds or days where floor 1 can be layout 13, with its luck ranges
+    fn check(settings: GameSettings) -> Option<Vec<(f64, f64)>> {
+        let hits: Vec<_> = compute_volcano_layouts(settings)
+            .into_iter()
+            .filter(|x| x.2[1] == 13)
+            .map(|x| (x.0, x.1))
+            .collect();
+        (!hits.is_empty()).then_some(hits)
+    }
+
+    #[test]
+    fn same_on_any_number_of_threads() {
+        let settings = GameSettings {
+            seed: 123456789,
+            ..GameSettings::new()
+        };
+        // a few chunks, the last one short
+        let seeds = -1500..=1200;
+        let days = 1..=2500;
+        let mut progress = vec![];
+        let one = search_seeds_on(1, settings, seeds.clone(), check, |x, _| progress.push(x));
+        assert!(one.len() > 10);
+        assert_eq!(progress, [1024, 2048, 2701]);
+        for num_threads in [2, 5] {
+            let mut last = (0, 0);
+            let many = search_seeds_on(num_threads, settings, seeds.clone(), check, |x, total| {
+                assert!(x > last.0);
+                last = (x, total);
+            });
+            assert_eq!(many, one);
+            assert_eq!(last, (2701, 2701));
+        }
+        let one = search_days_on(1, settings, days.clone(), check, |_, _| {});
+        assert!(one.len() > 10);
+        assert_eq!(search_days_on(4, settings, days, check, |_, _| {}), one);
+    }
+}
@@ -0,0 +1,294 @@
+use std::fmt::Write;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    compute_volcano_layouts, display_luck, format_date, generate_floor, generate_floor_in_range,
+    is_monster_floor, is_mushroom_floor, DataPackError, GameSettings, GeneratedFloor, Goodie,
+    MapTile, ProbabilityRange,
+};
+
+const STYLE: &str = "<style>\
+    text { font: bold 0.8px sans-serif; text-anchor: middle; pointer-events: none; }\
+    .enter rect { fill: #3fbf3f; } .exit rect { fill: #d23b3b; }\
+    .button rect { fill: #9a9a9a; } .monster rect { fill: #2fa6a6; }\
+    .piece { fill: none; stroke: #c8b88a; stroke-width: 0.15; }\
+    .tooth rect { fill: #f0f0e0; } .common rect { fill: #b07a3a; }\
+    .rare rect { fill: #e8c440; } .chance rect { fill: #e89a40; }\
+    .label { font: 2.5px sans-serif; text-anchor: start; }\
+    .label.head { font-weight: bold; }\
+    </style>";
+
+fn tile_color(tile: MapTile) -> &'static str {
+    match tile {
+        MapTile::Floor => "#3b2a3f",
+        MapTile::Lava => "#e2531d",
+        MapTile::Wall => "#000000",
+        MapTile::Enter => "#3b2a3f",
+        MapTile::Exit => "#3b2a3f",
+        MapTile::SetPiece => "#6e5a6a",
+        MapTile::SwitchLocation => "#3b2a3f",
+        MapTile::MonsterSpawn => "#3b2a3f",
+    }
+}
+
+fn goodie_label(goodie: &Goodie) -> (&'static str, &'static str) {
+    match goodie {
+        Goodie::DragonTooth => ("T", "tooth"),
+        Goodie::CommonChest(_) => ("C", "common"),
+        Goodie::RareChest(_) => ("R", "rare"),
+        Goodie::ChanceChest { .. } => ("?", "chance"),
+    }
+}
+
+// one tile sized marker with a hover tooltip
+fn marker(out: &mut String, x: i32, y: i32, class: &str, label: &str, title: &str) {
+    write!(
+        out,
+        "<g class=\"{class}\"><title>{title}</title>\
+         <rect x=\"{x}\" y=\"{y}\" width=\"1\" height=\"1\"/>\
+         <text x=\"{}\" y=\"{}\">{label}</text></g>",
+        x as f64 + 0.5,
+        y as f64 + 0.8,
+    )
+    .unwrap();
+}
+
+fn floor_title(lvl: usize, layout: u32, floor: &GeneratedFloor) -> String {
+    let mut out = format!("floor {}: layout {}", lvl, layout);
+    if floor.flip_x {
+        out += " (flipped)";
+    }
+    if is_mushroom_floor(layout) {
+        out += ", mushroom floor";
+    }
+    if is_monster_floor(layout) {
+        out += ", monster floor";
+    }
+    out
+}
+
+// the tiles and overlays of one floor, drawn in 64x64 tile units at the origin
+fn floor_contents(out: &mut String, floor: &GeneratedFloor) {
+    // merge horizontal runs of the same color to keep the file small
+    for y in 0..64 {
+        let mut x = 0;
+        while x < 64 {
+            let color = tile_color(floor.tiles[(x, y)]);
+            let start = x;
+            while x < 64 && tile_color(floor.tiles[(x, y)]) == color {
+                x += 1;
+            }
+            write!(
+                out,
+                "<rect x=\"{start}\" y=\"{y}\" width=\"{}\" height=\"1\" fill=\"{color}\"/>",
+                x - start
+            )
+            .unwrap();
+        }
+    }
+
+    for y in 0..64 {
+        for x in 0..64 {
+            match floor.tiles[(x, y)] {
+                MapTile::Enter => marker(out, x, y, "enter", "\u{2191}", "entrance"),
+                MapTile::Exit => marker(out, x, y, "exit", "\u{2193}", "stairs down"),
+                MapTile::SwitchLocation => {
+                    marker(out, x, y, "button", "B", "possible button location")
+                }
+                MapTile::MonsterSpawn => marker(out, x, y, "monster", "", "monster spawn"),
+                _ => {}
+            }
+        }
+    }
+
+    let mut pieces: Vec<(i32, i32, i32)> = floor.loot.iter().map(|x| x.0).collect();
+    pieces.dedup();
+    for (px, py, psize) in pieces {
+        let titles: Vec<_> = floor
+            .loot
+            .iter()
+            .filter(|x| x.0 == (px, py, psize))
+            .map(|x| x.1.describe())
+            .collect();
+        write!(
+            out,
+            "<rect class=\"piece\" x=\"{px}\" y=\"{py}\" width=\"{psize}\" height=\"{psize}\">\
+             <title>{}</title></rect>",
+            titles.join("\n")
+        )
+        .unwrap();
+    }
+    for (x, y, goodie) in floor.loot_tiles() {
+        let (label, class) = goodie_label(goodie);
+        marker(out, x, y, class, label, &goodie.describe());
+    }
+}
+
+/// Vector version of `render_map`, with the loot, buttons and stairs drawn on top. `scale` is
+/// the size of one tile in the svg's default width/height; the drawing itself is in tile units.
+pub fn render_map_svg(
+    settings: GameSettings,
+    lvl: i32,
+    layout: u32,
+    scale: u32,
+) -> Result<String, DataPackError> {
+    let floor = generate_floor(settings, lvl, layout)?;
+    let size = 64 * scale;
+    let mut out = String::new();
+    write!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 64 64\" \
+         width=\"{size}\" height=\"{size}\" shape-rendering=\"crispEdges\">"
+    )
+    .unwrap();
+    out += STYLE;
+    write!(
+        out,
+        "<title>{}</title>",
+        floor_title(lvl as usize, layout, &floor)
+    )
+    .unwrap();
+    floor_contents(&mut out, &floor);
+    out += "</svg>";
+    Ok(out)
+}
+
+#[wasm_bindgen(js_name = render_map_svg)]
+pub fn js_render_map_svg(
+    settings: GameSettings,
+    lvl: i32,
+    layout: u32,
+    scale: u32,
+) -> Result<String, JsError> {
+    Ok(render_map_svg(settings, lvl, layout, scale)?)
+}
+
+// short loot list for the overview labels, one line per distinct thing
+fn loot_summary(floor: &GeneratedFloor) -> Vec<String> {
+    let teeth = floor
+        .loot
+        .iter()
+        .filter(|x| x.1 == Goodie::DragonTooth)
+        .count();
+    let mut out = vec![];
+    if teeth > 0 {
+        out.push(format!("{}x Dragon Tooth", teeth));
+    }
+    for (_, goodie) in &floor.loot {
+        match goodie {
+            Goodie::DragonTooth => {}
+            Goodie::ChanceChest {
+                minluck,
+                common,
+                rare,
+            } => out.push(format!(
+                "luck > {:.4}: {}, else {}",
+                display_luck(*minluck),
+                rare,
+                common
+            )),
+            _ => out.push(goodie.to_string()),
+        }
+    }
+    if out.is_empty() {
+        out.push("[nothing]".to_string());
+    }
+    out
+}
+
+/// All ten floors of one luck branch next to each other, five per row, each labelled with its
+/// layout and loot.
+pub fn render_overview_svg(
+    settings: GameSettings,
+    minluck: f64,
+    maxluck: f64,
+    layouts: [u32; 10],
+    scale: u32,
+) -> Result<String, DataPackError> {
+    const COLS: usize = 5;
+    const GAP: usize = 4;
+    const LINE: usize = 3;
+    let floors: Vec<_> = layouts
+        .iter()
+        .enumerate()
+        .map(|(lvl, &layout)| {
+            generate_floor_in_range(settings, lvl as i32, layout, minluck, maxluck)
+        })
+        .collect::<Result<_, _>>()?;
+    let summaries: Vec<_> = floors.iter().map(loot_summary).collect();
+    let label_lines = 1 + summaries.iter().map(|x| x.len()).max().unwrap();
+    let panel_w = 64 + GAP;
+    let panel_h = 64 + label_lines * LINE + GAP;
+    let header_h = 2 * LINE + GAP;
+    let width = COLS * panel_w + GAP;
+    let height = header_h + floors.len().div_ceil(COLS) * panel_h;
+
+    let mut out = String::new();
+    write!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {width} {height}\" \
+         width=\"{}\" height=\"{}\" shape-rendering=\"crispEdges\">",
+        width as u32 * scale,
+        height as u32 * scale
+    )
+    .unwrap();
+    out += STYLE;
+    write!(
+        out,
+        "<rect width=\"{width}\" height=\"{height}\" fill=\"#ffffff\"/>\
+         <text class=\"label head\" x=\"{GAP}\" y=\"{}\">{}, luck {:.4} to {:.4}</text>",
+        GAP + LINE,
+        format_date(settings.days_played),
+        display_luck(minluck),
+        display_luck(maxluck),
+    )
+    .unwrap();
+    for (lvl, floor) in floors.iter().enumerate() {
+        let x = GAP + (lvl % COLS) * panel_w;
+        let y = header_h + (lvl / COLS) * panel_h;
+        write!(out, "<g transform=\"translate({x} {y})\">").unwrap();
+        floor_contents(&mut out, floor);
+        write!(
+            out,
+            "<text class=\"label head\" x=\"0\" y=\"{}\">{}</text>",
+            64 + LINE,
+            floor_title(lvl, layouts[lvl], floor)
+        )
+        .unwrap();
+        for (i, line) in summaries[lvl].iter().enumerate() {
+            write!(
+                out,
+                "<text class=\"label\" x=\"0\" y=\"{}\">{}</text>",
+                64 + (i + 2) * LINE,
+                line
+            )
+            .unwrap();
+        }
+        out += "</g>";
+    }
+    out += "</svg>";
+    Ok(out)
+}
+
+/// One overview per set of layouts that can happen on this day.
+pub fn render_overviews_svg(
+    settings: GameSettings,
+    scale: u32,
+) -> Result<ProbabilityRange<String>, DataPackError> {
+    compute_volcano_layouts(settings)
+        .into_iter()
+        .map(|(minluck, maxluck, layouts)| {
+            let svg = render_overview_svg(settings, minluck, maxluck, layouts, scale)?;
+            Ok((minluck, maxluck, svg))
+        })
+        .collect()
+}
+
+#[wasm_bindgen]
+pub fn dungeon_overviews(settings: GameSettings) -> Result<js_sys::Array, JsError> {
+    Ok(render_overviews_svg(settings, 2)?
+        .into_iter()
+        .map(|x| JsValue::from(x.2))
+        .collect())
+}
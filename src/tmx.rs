@@ -0,0 +1,201 @@
+use std::fmt::Write;
+
+use crate::{data_pack_for, generate_floor, DataPack, DataPackError, GameSettings, GeneratedFloor};
+
+// our own tile classes come first, the game's tilesheet after them
+const LAYOUT_TILES: u32 = 8;
+const FLIP_FLAGS: u32 = 0xe000_0000;
+
+fn escape_attr(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('"', "&quot;")
+}
+
+fn write_layer(out: &mut String, id: usize, name: &str, gids: &[u32]) {
+    writeln!(
+        out,
+        " <layer id=\"{id}\" name=\"{name}\" width=\"64\" height=\"64\">\n  <data encoding=\"csv\">"
+    )
+    .unwrap();
+    let rows: Vec<String> = gids
+        .chunks(64)
+        .map(|row| {
+            row.iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect();
+    writeln!(out, "{}", rows.join(",\n")).unwrap();
+    out.push_str("</data>\n </layer>\n");
+}
+
+/// Write a generated floor as a Tiled map. The "Layout" layer is the floor's tile classes (the
+/// same tiles as the map preview, from `maptiles_16.png`), and the game's own layers ("Back",
+/// "Buildings", ...) contain the set pieces that were picked, from the game's `volcano_dungeon`
+/// tilesheet. Both images are expected next to the .tmx file. Loot goes in an object layer.
+/// The set piece art comes from `data`, which should be the pack the floor was generated with;
+/// set pieces that it has no variant for are left out of the game's layers.
+pub fn floor_to_tmx(
+    data: &DataPack,
+    settings: GameSettings,
+    lvl: i32,
+    layout: u32,
+    floor: &GeneratedFloor,
+) -> String {
+    let mut out = String::new();
+    out += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+    out += "<map version=\"1.4\" orientation=\"orthogonal\" renderorder=\"right-down\" \
+            width=\"64\" height=\"64\" tilewidth=\"16\" tileheight=\"16\" infinite=\"0\">\n";
+    writeln!(
+        out,
+        " <properties>\n  \
+         <property name=\"seed\" type=\"int\" value=\"{}\"/>\n  \
+         <property name=\"days_played\" type=\"int\" value=\"{}\"/>\n  \
+         <property name=\"level\" type=\"int\" value=\"{}\"/>\n  \
+         <property name=\"layout\" type=\"int\" value=\"{}\"/>\n  \
+         <property name=\"flipped\" type=\"bool\" value=\"{}\"/>\n \
+         </properties>",
+        settings.seed, settings.days_played, lvl, layout, floor.flip_x
+    )
+    .unwrap();
+    writeln!(
+        out,
+        " <tileset firstgid=\"1\" name=\"layout\" tilewidth=\"16\" tileheight=\"16\" \
+         tilecount=\"{LAYOUT_TILES}\" columns=\"{LAYOUT_TILES}\">\n  \
+         <image source=\"maptiles_16.png\" width=\"128\" height=\"16\"/>\n </tileset>\n \
+         <tileset firstgid=\"{}\" name=\"dungeon\" tilewidth=\"16\" tileheight=\"16\" \
+         tilecount=\"576\" columns=\"16\">\n  \
+         <image source=\"volcano_dungeon.png\" width=\"256\" height=\"576\"/>\n </tileset>",
+        LAYOUT_TILES + 1
+    )
+    .unwrap();
+
+    let mut layer_id = 1;
+    let layout_gids: Vec<u32> = (0..64)
+        .flat_map(|y| (0..64).map(move |x| (x, y)))
+        .map(|pos| floor.tiles[pos] as u32 + 1)
+        .collect();
+    write_layer(&mut out, layer_id, "Layout", &layout_gids);
+
+    for name in data.tile_layer_names() {
+        let mut gids = vec![0_u32; 64 * 64];
+        for &((x, y, size), (row, col)) in &floor.set_pieces {
+            let Some((src_width, src)) = data.tile_layer(size, name) else {
+                continue;
+            };
+            let src_height = src.len() / src_width;
+            if (col + 1) * size > src_width as i32 || (row + 1) * size > src_height as i32 {
+                continue;
+            }
+            // squares by the map edge get cut off, like in load_set_pieces
+            for dy in 0..size.min(64 - y) {
+                for dx in 0..size.min(64 - x) {
+                    let src_x = (col * size + dx) as usize;
+                    let src_y = (row * size + dy) as usize;
+                    let gid = src[src_y * src_width + src_x];
+                    if gid != 0 {
+                        gids[((y + dy) * 64 + x + dx) as usize] =
+                            ((gid & !FLIP_FLAGS) + LAYOUT_TILES) | (gid & FLIP_FLAGS);
+                    }
+                }
+            }
+        }
+        layer_id += 1;
+        write_layer(&mut out, layer_id, name, &gids);
+    }
+
+    layer_id += 1;
+    writeln!(out, " <objectgroup id=\"{layer_id}\" name=\"Loot\">").unwrap();
+    for (i, (x, y, goodie)) in floor.loot_tiles().into_iter().enumerate() {
+        writeln!(
+            out,
+            "  <object id=\"{}\" name=\"{}\" x=\"{}\" y=\"{}\" width=\"16\" height=\"16\"/>",
+            i + 1,
+            escape_attr(&goodie.describe()),
+            x * 16,
+            y * 16
+        )
+        .unwrap();
+    }
+    out += " </objectgroup>\n</map>\n";
+    out
+}
+
+pub fn export_tmx(settings: GameSettings, lvl: i32, layout: u32) -> Result<String, DataPackError> {
+    let data = data_pack_for(&settings)?;
+    let floor = generate_floor(settings, lvl, layout)?;
+    Ok(floor_to_tmx(&data, settings, lvl, layout, &floor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the gids of one layer of an exported map, by (x, y)
+    fn layer(tmx: &str, name: &str) -> Vec<u32> {
+        let start = tmx.find(&format!("name=\"{}\"", name)).unwrap();
+        let data = &tmx[start..];
+        let data = &data[data.find("csv\">").unwrap() + 5..data.find("</data>").unwrap()];
+        data.split(',').map(|x| x.trim().parse().unwrap()).collect()
+    }
+
+    fn stamped(tmx: &str) -> Vec<(i32, i32, u32)> {
+        layer(tmx, "Buildings")
+            .into_iter()
+            .enumerate()
+            .filter(|x| x.1 != 0)
+            .map(|(i, gid)| (i as i32 % 64, i as i32 / 64, gid))
+            .collect()
+    }
+
+    fn test_floor(settings: GameSettings) -> GeneratedFloor {
+        let mut floor = generate_floor(settings, 1, 1).unwrap();
+        // a variant in the middle of the 3x3 sheet, one past its end like a data pack's sheet
+        // could give, and one running off the map
+        floor.set_pieces = vec![
+            ((10, 10, 3), (2, 1)),
+            ((20, 20, 3), (40, 40)),
+            ((62, 61, 3), (3, 3)),
+        ];
+        floor
+    }
+
+    #[test]
+    fn set_piece_tiles() {
+        let mut settings = GameSettings::new();
+        settings.days_played = 5;
+        let tmx = floor_to_tmx(&DataPack::builtin(), settings, 1, 1, &test_floor(settings));
+        assert!(tmx.ends_with("</map>\n"));
+        // Volcano_SetPieces_3.tmx's tiles, after the 8 of the layout tileset. nothing from
+        // the piece that's not in the sheet, and nothing of the cut off one wraps around
+        assert_eq!(
+            stamped(&tmx),
+            [
+                (12, 10, 460),
+                (11, 11, 480),
+                (12, 11, 476),
+                (12, 12, 492),
+                (63, 61, 480),
+                (63, 62, 460),
+                (62, 63, 479),
+                (63, 63, 492),
+            ]
+        );
+    }
+
+    #[test]
+    fn data_pack_art() {
+        let mut settings = GameSettings::new();
+        settings.days_played = 5;
+        let tmx = include_str!("../game_data/Volcano_SetPieces_3.tmx").replace(
+            "0,0,0,0,0,452,500,501,0,0,0,452,",
+            "0,0,0,0,0,453,500,501,0,0,0,452,",
+        );
+        let mut pack = DataPack::builtin();
+        pack.set_set_pieces(3, &tmx).unwrap();
+        let tmx = floor_to_tmx(&pack, settings, 1, 1, &test_floor(settings));
+        assert_eq!(stamped(&tmx)[0], (12, 10, 461));
+    }
+}
@@ -0,0 +1,171 @@
+import wasm_init, {
+    DataPack,
+    DayConditionKind,
+    GameSettings,
+    Language,
+    Observations,
+    ReportFormat,
+    TripPlanner,
+    days_csv,
+    days_csv_rows,
+    dungeon_overviews,
+    good_days,
+    map_data,
+    report,
+    update_data,
+    use_data_pack,
+} from "./pkg/sdv_volcano.js";
+
+// wasm objects can't be posted between threads, so settings travel as share codes and
+// observations as [level, layout] pairs
+export type Job =
+    | { kind: "update"; code: string; observed: [number, number][]; lang: Language }
+    | { kind: "map"; code: string; lvl: number; layout: number; lang: Language }
+    | { kind: "overview"; code: string }
+    | { kind: "report"; code: string; observed: [number, number][]; format: ReportFormat }
+    | { kind: "csv"; code: string; first: number; last: number }
+    | {
+          kind: "good_days";
+          code: string;
+          condition: DayConditionKind;
+          item: string;
+          teeth: number;
+          count: number;
+      }
+    | {
+          kind: "plan";
+          code: string;
+          first: number;
+          last: number;
+          max_trips: number;
+          wanted: [string, number][];
+          luck_lvls: number[];
+          lang: Language;
+      }
+    | { kind: "data_pack"; layouts: Uint8Array | null; set_pieces: [number, string][] };
+
+export type Reply =
+    | { id: number; result: any }
+    | { id: number; progress: [number, number] }
+    | { id: number; error: string };
+
+// days per call into wasm during a scan, i.e. how often progress gets reported
+const DAY_CHUNK = 7;
+// a year ahead is plenty to find anything that can happen at all
+const GOOD_DAYS_AHEAD = 28 * 4;
+
+const ctx = self as unknown as Worker;
+
+function get_observations(observed: [number, number][]): Observations {
+    const observations = new Observations();
+    for (const [lvl, layout] of observed) {
+        observations.add_layout(lvl, layout);
+    }
+    return observations;
+}
+
+function run(job: Job, progress: (done: number, total: number) => void): any {
+    switch (job.kind) {
+        case "update": {
+            const observations = get_observations(job.observed);
+            try {
+                return update_data(GameSettings.from_code(job.code), observations, job.lang);
+            } finally {
+                observations.free();
+            }
+        }
+        case "map":
+            return map_data(GameSettings.from_code(job.code), job.lvl, job.layout, job.lang);
+        case "overview":
+            return dungeon_overviews(GameSettings.from_code(job.code));
+        case "report": {
+            const observations = get_observations(job.observed);
+            try {
+                return report(GameSettings.from_code(job.code), observations, job.format);
+            } finally {
+                observations.free();
+            }
+        }
+        case "csv": {
+            const total = job.last - job.first + 1;
+            let out = "";
+            for (let day = job.first; day <= job.last; day += DAY_CHUNK) {
+                const last = Math.min(day + DAY_CHUNK - 1, job.last);
+                const settings = GameSettings.from_code(job.code);
+                // the first piece comes with the header
+                out += day == job.first
+                    ? days_csv(settings, day, last)
+                    : days_csv_rows(settings, day, last);
+                progress(last - job.first + 1, total);
+            }
+            return out;
+        }
+        case "good_days": {
+            const first = Math.max(GameSettings.from_code(job.code).days_played, 1);
+            const end = first + GOOD_DAYS_AHEAD;
+            const out: string[] = [];
+            for (let day = first; day < end && out.length < job.count; day += DAY_CHUNK) {
+                const num_days = Math.min(DAY_CHUNK, end - day);
+                const settings = GameSettings.from_code(job.code);
+                settings.days_played = day;
+                out.push(
+                    ...good_days(
+                        settings,
+                        job.condition,
+                        job.item,
+                        job.teeth,
+                        job.count - out.length,
+                        num_days,
+                    ),
+                );
+                progress(day + num_days - first, GOOD_DAYS_AHEAD);
+            }
+            return out;
+        }
+        case "plan": {
+            const planner = new TripPlanner(
+                GameSettings.from_code(job.code),
+                job.max_trips,
+                job.wanted.map((x) => x[0]),
+                new Float64Array(job.wanted.map((x) => x[1])),
+                new Uint32Array(job.luck_lvls),
+            );
+            try {
+                const total = job.last - job.first + 1;
+                for (let day = job.first; day <= job.last; day += DAY_CHUNK) {
+                    const last = Math.min(day + DAY_CHUNK - 1, job.last);
+                    planner.add_days(day, last);
+                    progress(last - job.first + 1, total);
+                }
+                return planner.lines(job.lang);
+            } finally {
+                planner.free();
+            }
+        }
+        case "data_pack": {
+            const pack = new DataPack();
+            if (job.layouts) {
+                pack.set_layouts(job.layouts);
+            }
+            for (const [size, tmx] of job.set_pieces) {
+                pack.set_set_pieces(size, tmx);
+            }
+            use_data_pack(pack);
+            pack.free();
+            return null;
+        }
+    }
+}
+
+const ready = wasm_init();
+
+ctx.onmessage = async (ev: MessageEvent<{ id: number; job: Job }>) => {
+    await ready;
+    const { id, job } = ev.data;
+    try {
+        const result = run(job, (done, total) => ctx.postMessage({ id, progress: [done, total] }));
+        ctx.postMessage({ id, result });
+    } catch (e) {
+        ctx.postMessage({ id, error: `${e}` });
+    }
+};